@@ -0,0 +1,342 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use glium::glutin::event::{MouseButton, VirtualKeyCode};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::input::Input;
+
+/// Something the player can do, triggered by the buttons bound to it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Mine,
+    Place,
+    /// Frees the cursor to use the menus, or captures it again to look around
+    ToggleCursor,
+    Quit,
+    #[serde(rename = "hotbar_1")]
+    Hotbar1,
+    #[serde(rename = "hotbar_2")]
+    Hotbar2,
+    #[serde(rename = "hotbar_3")]
+    Hotbar3,
+    #[serde(rename = "hotbar_4")]
+    Hotbar4,
+    #[serde(rename = "hotbar_5")]
+    Hotbar5,
+    #[serde(rename = "hotbar_6")]
+    Hotbar6,
+    #[serde(rename = "hotbar_7")]
+    Hotbar7,
+    #[serde(rename = "hotbar_8")]
+    Hotbar8,
+    #[serde(rename = "hotbar_9")]
+    Hotbar9,
+}
+
+impl Action {
+    /// Actions selecting the slots of the hotbar, in order
+    pub const HOTBAR: [Action; 9] = [
+        Action::Hotbar1,
+        Action::Hotbar2,
+        Action::Hotbar3,
+        Action::Hotbar4,
+        Action::Hotbar5,
+        Action::Hotbar6,
+        Action::Hotbar7,
+        Action::Hotbar8,
+        Action::Hotbar9,
+    ];
+}
+
+/// Key or mouse button
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Button {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+}
+
+/// Buttons that all have to be held to trigger an action. Written as the buttons joined by `+`, like `LControl+S`.
+/// Keys use the names of `VirtualKeyCode`, mouse buttons are `MouseLeft`, `MouseRight`, `MouseMiddle` or `Mouse<n>`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Chord(Vec<Button>);
+
+impl Chord {
+    pub fn new(buttons: Vec<Button>) -> Chord {
+        Chord(buttons)
+    }
+
+    pub fn key(key: VirtualKeyCode) -> Chord {
+        Chord(vec![Button::Key(key)])
+    }
+
+    pub fn mouse(button: MouseButton) -> Chord {
+        Chord(vec![Button::Mouse(button)])
+    }
+
+    pub fn buttons(&self) -> &[Button] {
+        &self.0
+    }
+
+    fn is_held(&self, input: &Input) -> bool {
+        !self.0.is_empty() && self.0.iter().all(|button| input.is_button_pressed(button))
+    }
+
+    /// Returns true if this chord holds every button of `other` and more
+    fn contains(&self, other: &Chord) -> bool {
+        self.0.len() > other.0.len() && other.0.iter().all(|button| self.0.contains(button))
+    }
+}
+
+impl TryFrom<String> for Chord {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Chord, String> {
+        text.split('+')
+            .map(|name| parse_button(name.trim()))
+            .collect::<Result<_, _>>()
+            .map(Chord)
+    }
+}
+
+impl From<Chord> for String {
+    fn from(chord: Chord) -> String {
+        chord.to_string()
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, button) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, "+")?;
+            }
+            match button {
+                Button::Key(key) => write!(f, "{:?}", key)?,
+                Button::Mouse(MouseButton::Other(n)) => write!(f, "Mouse{}", n)?,
+                Button::Mouse(button) => write!(f, "Mouse{:?}", button)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_button(name: &str) -> Result<Button, String> {
+    if let Some(mouse) = name.strip_prefix("Mouse") {
+        let button = match mouse {
+            "Left" => Some(MouseButton::Left),
+            "Right" => Some(MouseButton::Right),
+            "Middle" => Some(MouseButton::Middle),
+            n => n.parse().ok().map(MouseButton::Other),
+        };
+        return button
+            .map(Button::Mouse)
+            .ok_or_else(|| format!("unknown mouse button {}", name));
+    }
+    serde_json::from_value(serde_json::Value::String(name.to_string()))
+        .map(Button::Key)
+        .map_err(|_| format!("unknown key {}", name))
+}
+
+/// Buttons bound to every action. An action can have several bindings and is triggered by any of them.
+/// Actions missing from a config keep their default bindings
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Bindings(HashMap<Action, Vec<Chord>>);
+
+impl Default for Bindings {
+    fn default() -> Bindings {
+        use VirtualKeyCode::*;
+        let mut bindings = HashMap::new();
+        bindings.insert(Action::MoveForward, vec![Chord::key(W)]);
+        bindings.insert(Action::MoveBack, vec![Chord::key(S)]);
+        bindings.insert(Action::MoveLeft, vec![Chord::key(A)]);
+        bindings.insert(Action::MoveRight, vec![Chord::key(D)]);
+        bindings.insert(Action::Jump, vec![Chord::key(Space)]);
+        bindings.insert(Action::Mine, vec![Chord::mouse(MouseButton::Left)]);
+        bindings.insert(Action::Place, vec![Chord::mouse(MouseButton::Right)]);
+        bindings.insert(Action::ToggleCursor, vec![Chord::key(LAlt)]);
+        bindings.insert(Action::Quit, vec![Chord::key(Escape)]);
+        let keys = [Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9];
+        for (action, key) in Action::HOTBAR.into_iter().zip(keys) {
+            bindings.insert(action, vec![Chord::key(key)]);
+        }
+        Bindings(bindings)
+    }
+}
+
+impl<'de> Deserialize<'de> for Bindings {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Bindings, D::Error> {
+        let mut bindings = Bindings::default();
+        bindings
+            .0
+            .extend(HashMap::<Action, Vec<Chord>>::deserialize(deserializer)?);
+        Ok(bindings)
+    }
+}
+
+impl Bindings {
+    /// Returns the chords bound to `action`
+    pub fn get(&self, action: Action) -> &[Chord] {
+        self.0
+            .get(&action)
+            .map(|chords| chords.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Replaces the chords bound to `action`
+    pub fn set(&mut self, action: Action, chords: Vec<Chord>) {
+        self.0.insert(action, chords);
+    }
+}
+
+/// Tracks which actions are active based on the buttons held and the bindings
+#[derive(Default)]
+pub struct Actions {
+    bindings: Bindings,
+    active: HashSet<Action>,
+    previous: HashSet<Action>,
+    /// Lines scrolled that don't add up to a whole step yet
+    scroll: f32,
+    scroll_steps: i32,
+}
+
+impl Actions {
+    pub fn new(bindings: Bindings) -> Actions {
+        Actions {
+            bindings,
+            ..Default::default()
+        }
+    }
+
+    pub fn set_bindings(&mut self, bindings: Bindings) {
+        self.bindings = bindings;
+    }
+
+    /// Updates which actions are active from the buttons held. To be called once per tick.
+    /// A chord doesn't trigger its action while a larger chord containing it is held, so `LControl+S`
+    /// doesn't also move the player back
+    pub fn update(&mut self, input: &Input) {
+        let held: Vec<(Action, &Chord)> = self
+            .bindings
+            .0
+            .iter()
+            .flat_map(|(action, chords)| chords.iter().map(move |chord| (*action, chord)))
+            .filter(|(_, chord)| chord.is_held(input))
+            .collect();
+
+        self.scroll += input.get_scroll();
+        self.scroll_steps = self.scroll.trunc() as i32;
+        self.scroll -= self.scroll_steps as f32;
+
+        self.previous = std::mem::take(&mut self.active);
+        for (action, chord) in &held {
+            if !held.iter().any(|(_, other)| other.contains(chord)) {
+                self.active.insert(*action);
+            }
+        }
+    }
+
+    /// Returns true if `action` is active
+    pub fn is_active(&self, action: Action) -> bool {
+        self.active.contains(&action)
+    }
+
+    /// Returns the whole lines scrolled up in the last update, negative for down. Smaller
+    /// amounts, like touchpad scrolling, are added up over updates
+    pub fn scroll_steps(&self) -> i32 {
+        self.scroll_steps
+    }
+
+    /// Returns true if `action` became active in the last update
+    pub fn just_activated(&self, action: Action) -> bool {
+        self.active.contains(&action) && !self.previous.contains(&action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glium::glutin::event::{ElementState, MouseScrollDelta};
+
+    #[test]
+    fn chords_are_parsed_and_written() {
+        let chord = Chord::try_from("LControl + S".to_string()).unwrap();
+        assert_eq!(
+            chord.buttons(),
+            [
+                Button::Key(VirtualKeyCode::LControl),
+                Button::Key(VirtualKeyCode::S)
+            ]
+        );
+        assert_eq!(chord.to_string(), "LControl+S");
+        let mouse = Chord::try_from("MouseLeft+Mouse4".to_string()).unwrap();
+        assert_eq!(mouse.to_string(), "MouseLeft+Mouse4");
+        assert!(Chord::try_from("Shift+Nope".to_string()).is_err());
+    }
+
+    #[test]
+    fn missing_actions_keep_default_bindings() {
+        let bindings: Bindings =
+            serde_json::from_str(r#"{ "jump": ["E", "MouseMiddle"], "hotbar_1": [] }"#).unwrap();
+        assert_eq!(bindings.get(Action::Jump).len(), 2);
+        assert!(bindings.get(Action::Hotbar1).is_empty());
+        assert_eq!(
+            bindings.get(Action::MoveForward),
+            [Chord::key(VirtualKeyCode::W)]
+        );
+    }
+
+    #[test]
+    fn larger_chords_shadow_smaller_ones() {
+        let mut bindings = Bindings::default();
+        bindings.set(
+            Action::Quit,
+            vec![Chord::try_from("LControl+S".to_string()).unwrap()],
+        );
+        bindings.set(
+            Action::Jump,
+            vec![
+                Chord::key(VirtualKeyCode::Space),
+                Chord::key(VirtualKeyCode::J),
+            ],
+        );
+        let mut actions = Actions::new(bindings);
+        let mut input = Input::new();
+
+        input.process_keyboard_event(ElementState::Pressed, VirtualKeyCode::S);
+        actions.update(&input);
+        assert!(actions.is_active(Action::MoveBack) && actions.just_activated(Action::MoveBack));
+
+        input.process_keyboard_event(ElementState::Pressed, VirtualKeyCode::LControl);
+        actions.update(&input);
+        assert!(actions.just_activated(Action::Quit));
+        assert!(!actions.is_active(Action::MoveBack));
+
+        input.process_keyboard_event(ElementState::Pressed, VirtualKeyCode::J);
+        actions.update(&input);
+        assert!(actions.is_active(Action::Jump) && actions.is_active(Action::Quit));
+        assert!(!actions.just_activated(Action::Quit));
+    }
+
+    #[test]
+    fn scrolling_is_added_up_into_steps() {
+        let mut actions = Actions::default();
+        let mut input = Input::new();
+        let mut steps = Vec::new();
+        for delta in [0.4, 0.4, 0.4, -2.0, 0.0] {
+            input.update_scroll(MouseScrollDelta::LineDelta(0.0, delta));
+            actions.update(&input);
+            input.reset_deltas();
+            steps.push(actions.scroll_steps());
+        }
+        assert_eq!(steps, [0, 0, 1, -1, 0]);
+    }
+}
@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+
+use crate::crafting::Recipe;
+use crate::inventory::{Inventory, ItemStack};
+use crate::registry::Registry;
+
+/// Number of slots in a chest
+pub const CHEST_SLOTS: usize = 27;
+
+/// Kind of block entity created when a block is placed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockEntityKind {
+    Chest,
+    Furnace,
+    Sign,
+}
+
+/// Extra state stored for a single block position
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum BlockEntity {
+    Chest(Inventory),
+    Furnace(Furnace),
+    Sign(String),
+}
+
+impl BlockEntity {
+    pub fn new(kind: BlockEntityKind) -> BlockEntity {
+        match kind {
+            BlockEntityKind::Chest => BlockEntity::Chest(Inventory::new(CHEST_SLOTS)),
+            BlockEntityKind::Furnace => BlockEntity::Furnace(Furnace::default()),
+            BlockEntityKind::Sign => BlockEntity::Sign(String::new()),
+        }
+    }
+
+    /// Returns true if the block entity needs to be updated every tick
+    pub fn is_ticking(&self) -> bool {
+        matches!(self, BlockEntity::Furnace(_))
+    }
+
+    /// Updates the block entity. Returns true if its state changed
+    pub fn tick(&mut self, delta: f32, registry: &Registry) -> bool {
+        match self {
+            BlockEntity::Furnace(furnace) => furnace.tick(delta, registry),
+            _ => false,
+        }
+    }
+
+    /// Removes and returns every item stored in the block entity
+    pub fn take_contents(&mut self) -> Vec<ItemStack> {
+        match self {
+            BlockEntity::Chest(inventory) => (0..inventory.num_slots())
+                .filter_map(|slot| inventory.take(slot))
+                .collect(),
+            BlockEntity::Furnace(furnace) => {
+                furnace.progress = 0.0;
+                furnace.input.take().into_iter().chain(furnace.output.take()).collect()
+            }
+            BlockEntity::Sign(_) => Vec::new(),
+        }
+    }
+}
+
+/// Smelts the items in `input` one at a time into `output`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Furnace {
+    pub input: Option<ItemStack>,
+    pub output: Option<ItemStack>,
+    /// Seconds spent smelting the current item
+    pub progress: f32,
+}
+
+impl Furnace {
+    /// Returns the duration of the smelting recipe for the current input, if any
+    pub fn duration(&self, registry: &Registry) -> Option<f32> {
+        let input = self.input.as_ref()?;
+        match registry.recipes.find_smelting(input.id()) {
+            Some(Recipe::Smelting { duration, .. }) => Some(*duration),
+            _ => None,
+        }
+    }
+
+    fn tick(&mut self, delta: f32, registry: &Registry) -> bool {
+        let recipe = self
+            .input
+            .as_ref()
+            .and_then(|input| registry.recipes.find_smelting(input.id()));
+        let (output, duration) = match recipe {
+            Some(Recipe::Smelting { output, duration, .. }) => (output, *duration),
+            _ => {
+                let changed = self.progress != 0.0;
+                self.progress = 0.0;
+                return changed;
+            }
+        };
+        let mut result = match registry.items.stack(output.item, output.count) {
+            None => return false,
+            Some(result) => result,
+        };
+        let fits = match &self.output {
+            None => true,
+            Some(current) => {
+                current.id() == result.id() && current.count() + result.count() <= current.max_stack()
+            }
+        };
+        if !fits {
+            return false;
+        }
+
+        self.progress += delta;
+        if self.progress >= duration {
+            self.progress = 0.0;
+            if let Some(input) = self.input.as_mut() {
+                input.shrink(1);
+                if input.count() == 0 {
+                    self.input = None;
+                }
+            }
+            match self.output.as_mut() {
+                None => self.output = Some(result),
+                Some(current) => {
+                    current.merge(&mut result);
+                }
+            }
+        }
+        true
+    }
+}
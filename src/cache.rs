@@ -0,0 +1,182 @@
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+/// Counters of a `LruCache`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Number of lookups that found a usable entry
+    pub hits: u64,
+    /// Number of lookups that found no entry or an outdated one
+    pub misses: u64,
+    /// Number of entries that were found but outdated
+    pub invalidations: u64,
+    /// Number of entries removed to stay within the budget
+    pub evictions: u64,
+    pub entries: usize,
+    pub bytes: usize,
+    pub budget: usize,
+}
+
+/// Cache that holds values up to a budget of bytes, evicting the least recently inserted values first.
+/// Values are taken out of the cache when they are used, so the least recently inserted value is also the least recently used
+pub struct LruCache<K, V> {
+    entries: HashMap<K, Entry<V>>,
+    /// Keys ordered by the time they were inserted
+    order: BTreeMap<u64, K>,
+    next_tick: u64,
+    stats: CacheStats,
+}
+
+struct Entry<V> {
+    value: V,
+    bytes: usize,
+    tick: u64,
+}
+
+impl<K: Hash + Eq + Clone, V> LruCache<K, V> {
+    pub fn new(budget: usize) -> LruCache<K, V> {
+        LruCache {
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            next_tick: 0,
+            stats: CacheStats {
+                budget,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Inserts `value`, which takes up `bytes`, replacing the value of `key` if there is one.
+    /// Least recently used values are evicted until the cache is within its budget. Values larger than the budget are dropped
+    pub fn insert(&mut self, key: K, value: V, bytes: usize) {
+        self.remove(&key);
+        if bytes > self.stats.budget {
+            self.stats.evictions += 1;
+            return;
+        }
+        let tick = self.next_tick;
+        self.next_tick += 1;
+        self.order.insert(tick, key.clone());
+        self.entries.insert(key, Entry { value, bytes, tick });
+        self.stats.bytes += bytes;
+        self.evict();
+    }
+
+    /// Removes and returns the value of `key` if it is valid according to `is_valid`.
+    /// Invalid values are dropped. Counts as a hit if a valid value was found and a miss otherwise
+    pub fn take_if(&mut self, key: &K, is_valid: impl FnOnce(&V) -> bool) -> Option<V> {
+        match self.remove(key) {
+            Some(value) if is_valid(&value) => {
+                self.stats.hits += 1;
+                Some(value)
+            }
+            Some(_) => {
+                self.stats.invalidations += 1;
+                self.stats.misses += 1;
+                None
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Removes and returns the value of `key`. Counts as a hit if there was a value and a miss otherwise
+    pub fn take(&mut self, key: &K) -> Option<V> {
+        self.take_if(key, |_| true)
+    }
+
+    /// Removes the value of `key` without counting a hit or miss
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let entry = self.entries.remove(key)?;
+        self.order.remove(&entry.tick);
+        self.stats.bytes -= entry.bytes;
+        Some(entry.value)
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// Sets the number of bytes the cache may hold, evicting values if it holds more
+    pub fn set_budget(&mut self, budget: usize) {
+        self.stats.budget = budget;
+        self.evict();
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.entries.len(),
+            ..self.stats
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.stats.bytes > self.stats.budget {
+            let key = match self.order.values().next() {
+                None => return,
+                Some(key) => key.clone(),
+            };
+            self.remove(&key);
+            self.stats.evictions += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_inserted_first() {
+        let mut cache = LruCache::new(100);
+        cache.insert(1, "a", 40);
+        cache.insert(2, "b", 40);
+        cache.insert(3, "c", 40);
+        assert!(!cache.contains(&1));
+        assert!(cache.contains(&2) && cache.contains(&3));
+
+        // Taking and inserting again makes a value the most recently used one
+        let b = cache.take(&2).unwrap();
+        cache.insert(2, b, 40);
+        cache.insert(4, "d", 40);
+        assert!(!cache.contains(&3));
+        assert!(cache.contains(&2) && cache.contains(&4));
+
+        let stats = cache.stats();
+        assert_eq!((stats.entries, stats.bytes, stats.evictions), (2, 80, 2));
+    }
+
+    #[test]
+    fn counts_hits_misses_and_invalidations() {
+        let mut cache = LruCache::new(100);
+        cache.insert("x", 1, 10);
+        cache.insert("y", 2, 10);
+        assert_eq!(cache.take(&"x"), Some(1));
+        assert_eq!(cache.take(&"x"), None);
+        assert_eq!(cache.take_if(&"y", |v| *v == 3), None);
+        assert!(!cache.contains(&"y"));
+        assert_eq!(cache.remove(&"z"), None);
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.invalidations), (1, 2, 1));
+        assert_eq!((stats.entries, stats.bytes), (0, 0));
+    }
+
+    #[test]
+    fn budget_changes_evict() {
+        let mut cache = LruCache::new(100);
+        cache.insert(1, (), 30);
+        cache.insert(2, (), 30);
+        cache.insert(1, (), 50);
+        assert_eq!(cache.stats().bytes, 80);
+        cache.set_budget(60);
+        assert!(!cache.contains(&2) && cache.contains(&1));
+
+        // Values larger than the budget are not kept
+        cache.insert(3, (), 61);
+        assert!(!cache.contains(&3));
+        assert_eq!(cache.stats().bytes, 50);
+    }
+}
@@ -1,24 +1,74 @@
+use std::f32::consts::{PI, TAU};
+
 use crate::chunk::CHUNK_SIZE;
+use crate::pos::ChunkPos;
+
+/// Vertical field of view cameras start with, in radians
+pub const DEFAULT_FOV: f32 = 1.15 * PI / 3.0;
 
+#[derive(Clone)]
 pub struct Camera {
-    pub x: f32,
-    pub y: f32,
-    pub z: f32,
+    /// Position in world space. Double precision, so the camera doesn't jitter far from the origin
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
 
     pub pitch: f32,
     pub yaw: f32,
     pub roll: f32,
+    /// Vertical field of view in radians
+    pub fov: f32,
 
     pub projection: [[f32; 4]; 4],
 }
 
 impl Camera {
-    pub fn view_matrix(&self) -> [[f32; 4]; 4] {
-        let direction = &[
+    /// Returns the camera between this one (`alpha` = 0) and `next` (`alpha` = 1), to render between two ticks.
+    /// The yaw turns the shorter way around, so it doesn't spin when it wraps
+    pub fn interpolate(&self, next: &Camera, alpha: f32) -> Camera {
+        let lerp = |a: f32, b: f32| a + (b - a) * alpha;
+        let lerp_position = |a: f64, b: f64| a + (b - a) * alpha as f64;
+        let yaw_delta = (next.yaw - self.yaw + PI).rem_euclid(TAU) - PI;
+        Camera {
+            x: lerp_position(self.x, next.x),
+            y: lerp_position(self.y, next.y),
+            z: lerp_position(self.z, next.z),
+            pitch: lerp(self.pitch, next.pitch),
+            yaw: self.yaw + yaw_delta * alpha,
+            roll: lerp(self.roll, next.roll),
+            ..next.clone()
+        }
+    }
+
+    /// Returns the chunk the camera is in. Rendering is done relative to its origin
+    pub fn chunk(&self) -> ChunkPos {
+        ChunkPos::containing([self.x, self.y, self.z])
+    }
+
+    /// Returns the position of the camera relative to the origin of its chunk
+    pub fn relative_position(&self) -> [f32; 3] {
+        let origin = self.chunk().origin();
+        [
+            (self.x - origin.x as f64) as f32,
+            (self.y - origin.y as f64) as f32,
+            (self.z - origin.z as f64) as f32,
+        ]
+    }
+
+    /// Returns the unit vector the camera looks along
+    pub fn direction(&self) -> [f32; 3] {
+        [
             self.pitch.sin() * self.yaw.cos(),
             self.pitch.cos(),
             self.pitch.sin() * self.yaw.sin(),
-        ];
+        ]
+    }
+
+    /// Returns the view matrix relative to the origin of the camera's chunk, so the floats sent to the GPU
+    /// stay small far away from the world origin
+    pub fn view_matrix(&self) -> [[f32; 4]; 4] {
+        let [x, y, z] = self.relative_position();
+        let direction = &self.direction();
         const UP: &[f32; 3] = &[0.0, 1.0, 0.0];
         let f = {
             let f = direction;
@@ -46,9 +96,9 @@ impl Camera {
         ];
 
         let p = [
-            -self.x * s_norm[0] - self.y * s_norm[1] - self.z * s_norm[2],
-            -self.x * u[0] - self.y * u[1] - self.z * u[2],
-            -self.x * f[0] - self.y * f[1] - self.z * f[2],
+            -x * s_norm[0] - y * s_norm[1] - z * s_norm[2],
+            -x * u[0] - y * u[1] - z * u[2],
+            -x * f[0] - y * f[1] - z * f[2],
         ];
 
         [
@@ -59,11 +109,22 @@ impl Camera {
         ]
     }
 
+    /// Returns the projection matrix from the last `calculate_projection` multiplied by the view matrix.
+    /// Like the view matrix, it is relative to the origin of the camera's chunk
+    pub fn view_projection(&self) -> [[f32; 4]; 4] {
+        (nalgebra::Matrix4::from(self.projection) * nalgebra::Matrix4::from(self.view_matrix())).into()
+    }
+
+    /// Returns the frustum of the camera, relative to the origin of the camera's chunk
+    pub fn frustum(&self) -> Frustum {
+        Frustum::new(&self.view_projection(), self.chunk())
+    }
+
     pub fn calculate_projection(&mut self, target: &impl glium::Surface) -> [[f32; 4]; 4] {
         let (width, height) = target.get_dimensions();
         let aspect_ratio = height as f32 / width as f32;
 
-        let fov: f32 = 1.15 * std::f32::consts::PI / 3.0;
+        let fov = self.fov;
         let zfar = 1024.0;
         let znear = 0.1;
 
@@ -98,12 +159,14 @@ impl Plane {
     }
 }
 
+/// View frustum in the space of a view projection matrix relative to the origin of chunk `origin`
 pub struct Frustum {
     planes: [Plane; 6],
+    origin: ChunkPos,
 }
 
 impl Frustum {
-    pub fn new(vp: &[[f32; 4]; 4]) -> Self {
+    pub fn new(vp: &[[f32; 4]; 4], origin: ChunkPos) -> Self {
         // Left
         let p1 = Plane::new(
             vp[0][3] + vp[0][0],
@@ -154,15 +217,15 @@ impl Frustum {
 
         Frustum {
             planes: [p1, p2, p3, p4, p5, p6],
+            origin,
         }
     }
 
-    pub fn contains(&self, chunk_coord: &[i32; 3]) -> bool {
-        let (xs, ys, zs) = (
-            (chunk_coord[0] * CHUNK_SIZE.0 as i32) as f32,
-            (chunk_coord[1] as i32 * CHUNK_SIZE.1 as i32) as f32,
-            (chunk_coord[2] as i32 * CHUNK_SIZE.2 as i32) as f32,
-        );
+    /// Returns true if any part of a chunk may be inside of the frustum
+    pub fn contains(&self, chunk_coord: &ChunkPos) -> bool {
+        // Offset in integers first so only small values are converted to floats
+        let offset = (*chunk_coord - self.origin).origin();
+        let (xs, ys, zs) = (offset.x as f32, offset.y as f32, offset.z as f32);
         let (xf, yf, zf) = (
             xs + CHUNK_SIZE.0 as f32,
             ys + CHUNK_SIZE.1 as f32,
@@ -198,3 +261,84 @@ impl Frustum {
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Camera looking along +x with a 90° field of view
+    fn camera_at(x: f64, y: f64, z: f64) -> Camera {
+        let (near, far) = (0.1, 1024.0);
+        Camera {
+            x,
+            y,
+            z,
+            pitch: std::f32::consts::FRAC_PI_2,
+            yaw: 0.0,
+            roll: 0.0,
+            fov: DEFAULT_FOV,
+            projection: [
+                [1.0, 0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0, 0.0],
+                [0.0, 0.0, (far + near) / (far - near), 1.0],
+                [0.0, 0.0, -(2.0 * far * near) / (far - near), 0.0],
+            ],
+        }
+    }
+
+    #[test]
+    fn relative_position_is_inside_chunk() {
+        let camera = camera_at(-0.5, 1_000_016.0, -4_000_000.0);
+        assert_eq!(camera.chunk(), ChunkPos::new(-1, 31_250, -125_000));
+        assert_eq!(camera.relative_position(), [31.5, 16.0, 0.0]);
+
+        // Fractions that single precision can't hold that far out are kept
+        let camera = camera_at(30_000_000.3, -20_000_000.7, 0.1);
+        assert_eq!(camera.chunk(), ChunkPos::new(937_500, -625_001, 0));
+        let [x, y, z] = camera.relative_position();
+        assert!((x - 0.3).abs() < 1e-6, "{}", x);
+        assert!((y - 31.3).abs() < 1e-5, "{}", y);
+        assert!((z - 0.1).abs() < 1e-6, "{}", z);
+        let next = camera_at(30_000_000.5, -20_000_000.7, 0.1);
+        let [x, _, _] = camera.interpolate(&next, 0.5).relative_position();
+        assert!((x - 0.4).abs() < 1e-6, "{}", x);
+    }
+
+    #[test]
+    fn frustum_works_far_from_origin() {
+        for x in [0.0, -3_000_000.0, 30_000_000.0] {
+            let camera = camera_at(x + 16.0, 16.0, 16.0);
+            let frustum = camera.frustum();
+            let chunk = camera.chunk();
+            assert!(frustum.contains(&chunk));
+            assert!(frustum.contains(&chunk.offset(3, 0, 0)));
+            assert!(frustum.contains(&chunk.offset(3, 2, -2)));
+            assert!(!frustum.contains(&chunk.offset(-3, 0, 0)));
+            assert!(!frustum.contains(&chunk.offset(2, 0, 5)));
+            assert!(!frustum.contains(&chunk.offset(40, 0, 0)));
+        }
+    }
+
+    #[test]
+    fn interpolated_camera_is_between_ticks() {
+        let previous = camera_at(0.0, 10.0, -4.0);
+        let mut next = camera_at(2.0, 10.0, 4.0);
+        next.yaw = 1.0;
+        assert_eq!(previous.interpolate(&next, 0.0).x, previous.x);
+        let middle = previous.interpolate(&next, 0.5);
+        assert_eq!([middle.x, middle.y, middle.z], [1.0, 10.0, 0.0]);
+        assert!((middle.yaw - 0.5).abs() < 1e-6);
+        assert_eq!(previous.interpolate(&next, 1.0).z, next.z);
+
+        // Yaw takes the short way around when it wraps
+        let mut previous = previous;
+        previous.yaw = TAU - 0.1;
+        next.yaw = 0.1;
+        let middle = previous.interpolate(&next, 0.5);
+        assert!(middle.yaw.sin().abs() < 1e-5 && middle.yaw.cos() > 0.99, "{}", middle.yaw);
+        let quarter = previous.interpolate(&next, 0.25);
+        assert!((quarter.yaw - (TAU - 0.05)).abs() < 1e-5, "{}", quarter.yaw);
+        next.yaw = -TAU + 0.1;
+        assert!((previous.interpolate(&next, 0.25).yaw - (TAU - 0.05)).abs() < 1e-5);
+    }
+}
@@ -1,31 +1,38 @@
 use std::{
     collections::HashMap,
-    sync::{Arc, RwLock},
+    sync::atomic::{AtomicU64, Ordering},
+    sync::Arc,
 };
 
 use ndarray::Array3;
 use serde::*;
 
-use crate::loader::ChunkCoord;
+use crate::block_entity::BlockEntity;
+use crate::file_util::{read_from_file, save_to_file};
+use crate::item_entity::ItemEntity;
+use crate::pos::{BlockPos, ChunkPos, LocalPos, NEIGHBOR_OFFSETS};
 
 pub const CHUNK_SIZE: (usize, usize, usize) = (32, 32, 32);
 
+/// Source of chunk versions. Shared by every chunk so a version is never reused, even by a chunk that was unloaded and loaded again
+static NEXT_VERSION: AtomicU64 = AtomicU64::new(1);
+
+fn next_version() -> u64 {
+    NEXT_VERSION.fetch_add(1, Ordering::Relaxed)
+}
+
 #[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Block {
     id: u16,
-    health: f32,
 }
 
 impl Block {
-    pub fn new(id: u16, health: f32) -> Block {
-        Block {
-            id,
-            health,
-        }
+    pub fn new(id: u16) -> Block {
+        Block { id }
     }
 
     pub fn air() -> Block {
-        Block { id: 0, health: 0.0 }
+        Block { id: 0 }
     }
 
     pub fn is_air(&self) -> bool {
@@ -35,17 +42,14 @@ impl Block {
     pub fn id(&self) -> u16 {
         self.id
     }
-
-    pub fn health(&self) -> f32 {
-        self.health
-    }
 }
 
-struct Faces;
-struct Face {
-    points: &'static [(i32, i32, i32); 4],
-    normal: (i32, i32, i32),
-    face_id: u8,
+/// The 6 faces of a block
+pub struct Faces;
+pub struct Face {
+    pub points: &'static [(i32, i32, i32); 4],
+    pub normal: (i32, i32, i32),
+    pub face_id: u8,
 }
 
 impl Faces {
@@ -79,6 +83,23 @@ impl Faces {
         normal: (0, 0, -1),
         face_id: 5,
     };
+    pub const ALL: [&'static Face; 6] = [
+        Faces::RIGHT,
+        Faces::LEFT,
+        Faces::BOTTOM,
+        Faces::TOP,
+        Faces::FRONT,
+        Faces::BACK,
+    ];
+}
+
+/// How faces bordering a neighbor that isn't loaded yet are meshed until the neighbor is loaded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnloadedNeighbor {
+    /// Hide the faces, as if the neighbor was solid. Builds fewer faces, but leaves holes at the edge of the loaded area
+    Solid,
+    /// Show the faces, as if the neighbor was air
+    Air,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -89,47 +110,304 @@ pub struct Vertex {
 
 implement_vertex!(Vertex, position, tex_coords);
 
+/// Vertices and indices of a mesh, or of a section of one
+pub type MeshData = (Vec<Vertex>, Vec<u16>);
+
 pub struct Chunk {
-    coord: ChunkCoord,
-    block_data: Option<Box<ndarray::Array3<Block>>>,
+    coord: ChunkPos,
+    /// Shared with the snapshots of the chunk, copied when the chunk is edited while a snapshot holds it
+    block_data: Option<Arc<ndarray::Array3<Block>>>,
+    block_entities: HashMap<LocalPos, BlockEntity>,
+    /// Dropped items inside of the chunk
+    items: Vec<ItemEntity>,
     needs_update: bool,
+    /// True if the chunk changed since it was generated or loaded and needs to be saved
+    modified: bool,
+    /// Changes whenever a block of the chunk changes, used to tell if a mesh is outdated
+    version: u64,
+    /// Change whenever a block on the face of the chunk changes, in the order of `NEIGHBOR_OFFSETS`.
+    /// Used to tell if the mesh of a neighbor is outdated
+    border_versions: [u64; 6],
+}
+
+/// Format a chunk is saved in
+#[derive(Serialize, Deserialize)]
+struct ChunkSave {
+    block_data: Option<Box<ndarray::Array3<Block>>>,
+    block_entities: Vec<(LocalPos, BlockEntity)>,
+    items: Vec<ItemEntity>,
 }
 
 impl Chunk {
-    pub fn empty(coord: ChunkCoord) -> Chunk {
+    pub fn empty(coord: ChunkPos) -> Chunk {
         Chunk {
             coord,
             block_data: None,
+            block_entities: HashMap::new(),
+            items: Vec::new(),
             needs_update: false,
+            modified: false,
+            version: next_version(),
+            border_versions: [next_version(); 6],
         }
     }
 
-    pub fn new(coord: ChunkCoord) -> Chunk {
+    pub fn from_data(coord: ChunkPos, data: Array3<Block>) -> Chunk {
         Chunk {
             coord,
-            block_data: None,
+            block_data: Some(Arc::new(data)),
+            block_entities: HashMap::new(),
+            items: Vec::new(),
             needs_update: true,
+            modified: false,
+            version: next_version(),
+            border_versions: [next_version(); 6],
         }
     }
 
-    pub fn from_data(coord: ChunkCoord, data: Box<Array3<Block>>) -> Chunk {
-        Chunk {
-            coord,
-            block_data: Some(data),
-            needs_update: true,
+    /// Loads a chunk saved with `save`. Returns none if the file doesn't exist or is invalid
+    pub fn load(coord: ChunkPos, path: &str) -> Option<Chunk> {
+        let data = read_from_file(path)?;
+        match bincode::deserialize::<ChunkSave>(data.as_slice()) {
+            Err(e) => {
+                println!("Error reading chunk: {e}");
+                None
+            }
+            Ok(save) => Some(Chunk {
+                coord,
+                block_data: save.block_data.map(|data| Arc::new(*data)),
+                block_entities: save.block_entities.into_iter().collect(),
+                items: save.items,
+                needs_update: true,
+                modified: false,
+                version: next_version(),
+                border_versions: [next_version(); 6],
+            }),
+        }
+    }
+
+    /// Saves the blocks, block entities and dropped items of the chunk to a file.
+    /// The chunk is no longer modified once it is saved
+    pub fn save(&mut self, path: &str) {
+        let save = ChunkSave {
+            block_data: self.block_data.as_deref().cloned().map(Box::new),
+            block_entities: self
+                .block_entities
+                .iter()
+                .map(|(pos, entity)| (*pos, entity.clone()))
+                .collect(),
+            items: self.items.clone(),
+        };
+        match bincode::serialize(&save) {
+            Ok(bytes) => {
+                if save_to_file(bytes.as_slice(), path) {
+                    self.modified = false;
+                }
+            }
+            Err(e) => println!("Error serializing chunk: {}", e),
+        }
+    }
+
+    pub fn set_block(&mut self, pos: LocalPos, block: Block) -> bool {
+        let mut needs_update = false;
+
+        match self.block_data {
+            None => {
+                self.block_data = Some(Arc::new(ndarray::Array3::default(CHUNK_SIZE)));
+                needs_update = true;
+            },
+            Some(_) => {
+                if self.block_data.as_ref().unwrap()[pos.index()] != block {
+                    self.needs_update = true;
+                    needs_update = true;
+                }
+            }
+        }
+
+        // Copies the blocks if a snapshot still holds them, so edits never wait for a mesh to be built
+        Arc::make_mut(self.block_data.as_mut().unwrap())[pos.index()] = block;
+        if needs_update {
+            self.version = next_version();
+            for (version, on_border) in self.border_versions.iter_mut().zip(pos.on_border()) {
+                if on_border {
+                    *version = self.version;
+                }
+            }
+        }
+
+        needs_update
+    }
+
+    pub fn get_block(&self, pos: LocalPos) -> Option<Block> {
+        match &self.block_data {
+            None => Some(Block::air()),
+            Some(data) => Some(data[pos.index()].clone()),
+        }
+    }
+
+    pub fn coord(&self) -> ChunkPos {
+        self.coord
+    }
+
+    /// Returns an immutable copy of the blocks and versions of the chunk
+    pub fn snapshot(&self) -> ChunkSnapshot {
+        ChunkSnapshot {
+            coord: self.coord,
+            block_data: self.block_data.clone(),
+            version: self.version,
+            border_versions: self.border_versions,
+        }
+    }
+
+    /// Returns the blocks of the chunk. Returns none if the chunk only contains air
+    pub fn data(&self) -> Option<&ndarray::Array3<Block>> {
+        self.block_data.as_deref()
+    }
+
+    pub fn get_block_entity(&self, pos: LocalPos) -> Option<&BlockEntity> {
+        self.block_entities.get(&pos)
+    }
+
+    pub fn get_block_entity_mut(&mut self, pos: LocalPos) -> Option<&mut BlockEntity> {
+        self.block_entities.get_mut(&pos)
+    }
+
+    /// Sets or removes the block entity at `pos`, returning the previous one
+    pub fn set_block_entity(
+        &mut self,
+        pos: LocalPos,
+        entity: Option<BlockEntity>,
+    ) -> Option<BlockEntity> {
+        match entity {
+            Some(entity) => self.block_entities.insert(pos, entity),
+            None => self.block_entities.remove(&pos),
+        }
+    }
+
+    pub fn block_entities_mut(&mut self) -> impl Iterator<Item = (&LocalPos, &mut BlockEntity)> {
+        self.block_entities.iter_mut()
+    }
+
+    pub fn has_block_entities(&self) -> bool {
+        !self.block_entities.is_empty()
+    }
+
+    /// Adds a dropped item to the chunk and marks it as modified
+    pub fn add_item(&mut self, item: ItemEntity) {
+        self.items.push(item);
+        self.modified = true;
+    }
+
+    /// Removes and returns every dropped item in the chunk, without marking it as modified
+    pub fn take_items(&mut self) -> Vec<ItemEntity> {
+        std::mem::take(&mut self.items)
+    }
+
+    /// Puts back dropped items after simulating them, marking the chunk as modified if `changed`
+    pub fn put_items(&mut self, items: Vec<ItemEntity>, changed: bool) {
+        self.items.extend(items);
+        if changed {
+            self.modified = true;
+        }
+    }
+
+    pub fn items(&self) -> &[ItemEntity] {
+        &self.items
+    }
+
+    pub fn has_items(&self) -> bool {
+        !self.items.is_empty()
+    }
+
+    /// Returns the version of the blocks of the chunk. Two chunks with the same version have the same blocks
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Returns the version of the blocks on face `face` (in the order of `NEIGHBOR_OFFSETS`) of the chunk
+    pub fn border_version(&self, face: usize) -> u64 {
+        self.border_versions[face]
+    }
+
+    /// Returns roughly how many bytes of memory the chunk takes up
+    pub fn memory_size(&self) -> usize {
+        let blocks = match self.block_data {
+            None => 0,
+            Some(_) => CHUNK_SIZE.0 * CHUNK_SIZE.1 * CHUNK_SIZE.2 * std::mem::size_of::<Block>(),
+        };
+        std::mem::size_of::<Chunk>()
+            + blocks
+            + self.block_entities.len() * std::mem::size_of::<(LocalPos, BlockEntity)>()
+            + self.items.len() * std::mem::size_of::<ItemEntity>()
+    }
+
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    pub fn set_modified(&mut self) {
+        self.modified = true;
+    }
+
+    pub fn set_updated(&mut self) {
+        self.needs_update = false;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.block_data.is_none()
+    }
+
+    pub fn needs_update(&self) -> bool {
+        self.needs_update
+    }
+
+    pub fn request_update(&mut self) {
+        self.needs_update = true;
+    }
+}
+
+/// Immutable copy of the blocks of a chunk, given to worker threads so they never hold a lock on the chunk.
+/// Cheap to create, since the blocks are shared with the chunk until it is edited
+#[derive(Clone)]
+pub struct ChunkSnapshot {
+    coord: ChunkPos,
+    block_data: Option<Arc<ndarray::Array3<Block>>>,
+    version: u64,
+    border_versions: [u64; 6],
+}
+
+impl ChunkSnapshot {
+    pub fn coord(&self) -> ChunkPos {
+        self.coord
+    }
+
+    /// Returns the version of the chunk when the snapshot was taken
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Returns the version of the blocks on face `face` (in the order of `NEIGHBOR_OFFSETS`) when the snapshot was taken
+    pub fn border_version(&self, face: usize) -> u64 {
+        self.border_versions[face]
+    }
+
+    pub fn get_block(&self, pos: LocalPos) -> Option<Block> {
+        match &self.block_data {
+            None => Some(Block::air()),
+            Some(data) => Some(data[pos.index()].clone()),
         }
     }
 
     fn add_face(
         &self,
-        vertices: &mut Vec<Vertex>,
-        indices: &mut Vec<u16>,
-        (i, j, k): (usize, usize, usize),
+        (vertices, indices): &mut MeshData,
+        pos: LocalPos,
         face: &Face,
         texture_map_info: &Arc<HashMap<u16, [[[f32; 2]; 4]; 6]>>,
     ) {
         const FACE_INDICES: &[i32; 6] = &[2, 1, 0, 0, 3, 2];
         let mut mesh_face_index_loc: [usize; 4] = [0; 4];
+        let [i, j, k] = pos.index();
 
         for c in 0..4 {
             let (fx, fy, fz) = face.points.get(c).unwrap();
@@ -145,7 +423,7 @@ impl Chunk {
                     | (face.normal.2 as u32) << 20,
                 tex_coords: {
                     let face_tex_coords = texture_map_info
-                        .get(&self.get_block((i, j, k)).unwrap().id)
+                        .get(&self.block_data.as_ref().unwrap()[(i, j, k)].id)
                         .unwrap()[face.face_id as usize];
                     [face_tex_coords[c][0],face_tex_coords[c][1]]
                 },
@@ -157,350 +435,158 @@ impl Chunk {
         }
     }
 
+    /// Generates the mesh of the chunk. Faces bordering a neighbor that isn't loaded are meshed according to `unloaded`
+    /// and put in a separate section for that neighbor, so they can be rebuilt with `gen_border_mesh` once it is loaded.
+    /// Returns the main section and the sections of the unloaded neighbors, in the order of `NEIGHBOR_OFFSETS`
     pub fn gen_mesh(
         &self,
-        neighbors: [Arc<RwLock<Chunk>>;6],
+        neighbors: &[Option<ChunkSnapshot>; 6],
+        unloaded: UnloadedNeighbor,
         texture_map_info: &Arc<HashMap<u16, [[[f32; 2]; 4]; 6]>>,
-    ) -> (Vec<Vertex>, Vec<u16>) {
-        let mut vertices = Vec::with_capacity(CHUNK_SIZE.0 * CHUNK_SIZE.1 * CHUNK_SIZE.2);
-        let mut indices = Vec::with_capacity(CHUNK_SIZE.0 * CHUNK_SIZE.1 * CHUNK_SIZE.2 * 3);
-
-        for i in 0..CHUNK_SIZE.0 {
-            for j in 0..CHUNK_SIZE.1 {
-                for k in 0..CHUNK_SIZE.2 {
-                    // Check if block or air
-                    if self.block_data.as_ref().unwrap().get((i, j, k)).unwrap().id != 0 {
-                        // Check adjacent blocks
-
-                        // Add right face to mesh
-                        if i == CHUNK_SIZE.0 - 1
-                            || self
-                                .block_data
-                                .as_ref()
-                                .unwrap()
-                                .get((i + 1, j, k))
-                                .unwrap()
-                                .id
-                                == 0
-                        {
-                            // Check neighbor chunk if block is on edge
-                            if i == CHUNK_SIZE.0 - 1 {
-                                let neighbor = neighbors[0].read().unwrap();
-                                if neighbor.block_data.is_none()
-                                    || neighbor
-                                        .block_data
-                                        .as_ref()
-                                        .unwrap()
-                                        .get((0, j, k))
-                                        .unwrap_or(&Block { id: 0, health: 0.0 })
-                                        .id
-                                        == 0
-                                {
-                                    self.add_face(
-                                        &mut vertices,
-                                        &mut indices,
-                                        (i, j, k),
-                                        Faces::RIGHT,
-                                        texture_map_info,
-                                    );
-                                }
-                            } else {
-                                self.add_face(
-                                    &mut vertices,
-                                    &mut indices,
-                                    (i, j, k),
-                                    Faces::RIGHT,
-                                    texture_map_info,
-                                );
-                            }
-                        }
-
-                        // Add left face to mesh
-                        if i == 0
-                            || self
-                                .block_data
-                                .as_ref()
-                                .unwrap()
-                                .get((i - 1, j, k))
-                                .unwrap()
-                                .id
-                                == 0
-                        {
-                            if i == 0 {
-                                let neighbor = neighbors[1].read().unwrap();
-                                if neighbor.block_data.is_none()
-                                    || neighbor
-                                        .block_data
-                                        .as_ref()
-                                        .unwrap()
-                                        .get((CHUNK_SIZE.0 - 1, j, k))
-                                        .unwrap_or(&Block { id: 0, health: 0.0 })
-                                        .id
-                                        == 0
-                                {
-                                    self.add_face(
-                                        &mut vertices,
-                                        &mut indices,
-                                        (i, j, k),
-                                        Faces::LEFT,
-                                        texture_map_info,
-                                    );
-                                }
-                            } else {
-                                self.add_face(
-                                    &mut vertices,
-                                    &mut indices,
-                                    (i, j, k),
-                                    Faces::LEFT,
-                                    texture_map_info,
-                                );
-                            }
-                        }
-
-                        // Add bottom face to mesh
-                        if j == 0
-                            || self
-                                .block_data
-                                .as_ref()
-                                .unwrap()
-                                .get((i, j - 1, k))
-                                .unwrap()
-                                .id
-                                == 0
-                        {
-                            if j == 0 {
-                                let neighbor = neighbors[2].read().unwrap();
-                                if neighbor.block_data.is_none()
-                                    || neighbor
-                                        .block_data
-                                        .as_ref()
-                                        .unwrap()
-                                        .get((i, CHUNK_SIZE.1 - 1, k))
-                                        .unwrap_or(&Block { id: 0, health: 0.0 })
-                                        .id
-                                        == 0
-                                {
-                                    self.add_face(
-                                        &mut vertices,
-                                        &mut indices,
-                                        (i, j, k),
-                                        Faces::BOTTOM,
-                                        texture_map_info,
-                                    );
-                                }
-                            } else {
-                                self.add_face(
-                                    &mut vertices,
-                                    &mut indices,
-                                    (i, j, k),
-                                    Faces::BOTTOM,
-                                    texture_map_info,
-                                );
-                            }
-                        }
-
-                        // Add top face to mesh
-                        if j == CHUNK_SIZE.1 - 1
-                            || self
-                                .block_data
-                                .as_ref()
-                                .unwrap()
-                                .get((i, j + 1, k))
-                                .unwrap()
-                                .id
-                                == 0
-                        {
-                            if j == CHUNK_SIZE.1 - 1 {
-                                let neighbor = neighbors[3].read().unwrap();
-                                if neighbor.block_data.is_none()
-                                    || neighbor
-                                        .block_data
-                                        .as_ref()
-                                        .unwrap()
-                                        .get((i, 0, k))
-                                        .unwrap_or(&Block { id: 0, health: 0.0 })
-                                        .id
-                                        == 0
-                                {
-                                    self.add_face(
-                                        &mut vertices,
-                                        &mut indices,
-                                        (i, j, k),
-                                        Faces::TOP,
-                                        texture_map_info,
-                                    );
-                                }
-                            } else {
-                                self.add_face(
-                                    &mut vertices,
-                                    &mut indices,
-                                    (i, j, k),
-                                    Faces::TOP,
-                                    texture_map_info,
-                                );
-                            }
-                        }
-
-                        // Add front face to mesh
-                        if k == CHUNK_SIZE.2 - 1
-                            || self
-                                .block_data
-                                .as_ref()
-                                .unwrap()
-                                .get((i, j, k + 1))
-                                .unwrap()
-                                .id
-                                == 0
-                        {
-                            if k == CHUNK_SIZE.2 - 1 {
-                                let neighbor = neighbors[4].read().unwrap();
-                                if neighbor.block_data.is_none()
-                                    || neighbor
-                                        .block_data
-                                        .as_ref()
-                                        .unwrap()
-                                        .get((i, j, 0))
-                                        .unwrap_or(&Block { id: 0, health: 0.0 })
-                                        .id
-                                        == 0
-                                {
-                                    self.add_face(
-                                        &mut vertices,
-                                        &mut indices,
-                                        (i, j, k),
-                                        Faces::FRONT,
-                                        texture_map_info,
-                                    );
-                                }
-                            } else {
-                                self.add_face(
-                                    &mut vertices,
-                                    &mut indices,
-                                    (i, j, k),
-                                    Faces::FRONT,
-                                    texture_map_info,
-                                );
-                            }
+    ) -> (MeshData, [Option<MeshData>; 6]) {
+        let mut main = (
+            Vec::with_capacity(CHUNK_SIZE.0 * CHUNK_SIZE.1 * CHUNK_SIZE.2),
+            Vec::with_capacity(CHUNK_SIZE.0 * CHUNK_SIZE.1 * CHUNK_SIZE.2 * 3),
+        );
+        let mut borders: [Option<MeshData>; 6] = Default::default();
+        for (border, neighbor) in borders.iter_mut().zip(neighbors) {
+            if neighbor.is_none() {
+                *border = Some(MeshData::default());
+            }
+        }
+        let data = match &self.block_data {
+            None => return (main, borders),
+            Some(data) => data,
+        };
+
+        for pos in LocalPos::all() {
+            if data[pos.index()].is_air() {
+                continue;
+            }
+            for (face, offset) in NEIGHBOR_OFFSETS.iter().enumerate() {
+                match pos.offset(*offset) {
+                    Some(next) => {
+                        if data[next.index()].is_air() {
+                            self.add_face(&mut main, pos, Faces::ALL[face], texture_map_info);
                         }
-
-                        // Add back face to mesh
-                        if k == 0
-                            || self
-                                .block_data
-                                .as_ref()
-                                .unwrap()
-                                .get((i, j, k - 1))
-                                .unwrap()
-                                .id
-                                == 0
-                        {
-                            if k == 0 {
-                                let neighbor = neighbors[5].read().unwrap();
-                                if neighbor.block_data.is_none()
-                                    || neighbor
-                                        .block_data
-                                        .as_ref()
-                                        .unwrap()
-                                        .get((i, j, CHUNK_SIZE.2 - 1))
-                                        .unwrap_or(&Block { id: 0, health: 0.0 })
-                                        .id
-                                        == 0
-                                {
-                                    self.add_face(
-                                        &mut vertices,
-                                        &mut indices,
-                                        (i, j, k),
-                                        Faces::BACK,
-                                        texture_map_info,
-                                    );
-                                }
-                            } else {
-                                self.add_face(
-                                    &mut vertices,
-                                    &mut indices,
-                                    (i, j, k),
-                                    Faces::BACK,
-                                    texture_map_info,
-                                );
-                            }
+                    }
+                    // Block is on the border, so the face depends on the neighbor
+                    None => {
+                        if self.border_face_visible(pos, face, neighbors[face].as_ref(), unloaded) {
+                            let section = borders[face].as_mut().unwrap_or(&mut main);
+                            self.add_face(section, pos, Faces::ALL[face], texture_map_info);
                         }
                     }
                 }
             }
         }
 
-        (vertices, indices)
+        (main, borders)
     }
 
-    pub fn set_block(&mut self, (i, j, k): (usize, usize, usize), block: Block) -> bool {
-        let mut needs_update = false;
-
-        match self.block_data {
-            None => {
-                self.block_data = Some(Box::new(ndarray::Array3::default(CHUNK_SIZE)));
-                needs_update = true;
-            },
-            Some(_) => {
-                if self.block_data.as_ref().unwrap()[[i, j, k]] != block {
-                    self.needs_update = true;
-                    needs_update = true;
+    /// Generates the section of the mesh with the faces bordering the neighbor at `face` (in the order of `NEIGHBOR_OFFSETS`)
+    pub fn gen_border_mesh(
+        &self,
+        face: usize,
+        neighbor: Option<&ChunkSnapshot>,
+        unloaded: UnloadedNeighbor,
+        texture_map_info: &Arc<HashMap<u16, [[[f32; 2]; 4]; 6]>>,
+    ) -> MeshData {
+        let mut section = MeshData::default();
+        if let Some(data) = &self.block_data {
+            for pos in LocalPos::all().filter(|pos| pos.on_border()[face]) {
+                if !data[pos.index()].is_air() && self.border_face_visible(pos, face, neighbor, unloaded) {
+                    self.add_face(&mut section, pos, Faces::ALL[face], texture_map_info);
                 }
             }
         }
-
-        self.block_data.as_mut().unwrap()[[i, j, k]] = block;
-
-        needs_update
+        section
     }
 
-    pub fn get_block(&self, (i, j, k): (usize, usize, usize)) -> Option<Block> {
-        match &self.block_data {
-            None => Some(Block { id: 0, health: 69.0 }),
-            Some(data) => Some(data[(i, j, k)].clone()),
+    /// Returns true if the face of a block on the border facing the neighbor at `face` is visible
+    fn border_face_visible(
+        &self,
+        pos: LocalPos,
+        face: usize,
+        neighbor: Option<&ChunkSnapshot>,
+        unloaded: UnloadedNeighbor,
+    ) -> bool {
+        match neighbor {
+            None => unloaded == UnloadedNeighbor::Air,
+            Some(neighbor) => {
+                let adjacent = BlockPos::from_parts(self.coord, pos).neighbors()[face].local();
+                neighbor.get_block(adjacent).map(|block| block.is_air()).unwrap_or(true)
+            }
         }
     }
+}
 
-    // pub fn get_pos(&self) -> (i32, i32, i32) {
-    //     self.position
-    // }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    pub fn get_data_mut(&mut self) -> &mut Option<Box<ndarray::Array3<Block>>> {
-        &mut self.block_data
-    }
+    const FACE_VERTICES: usize = 4;
 
-    pub fn get_data(&self) -> &Option<Box<ndarray::Array3<Block>>> {
-        &self.block_data
+    fn texture_info() -> Arc<HashMap<u16, [[[f32; 2]; 4]; 6]>> {
+        Arc::new((0..4).map(|id| (id, [[[0.0; 2]; 4]; 6])).collect())
     }
 
-    pub fn set_updated(&mut self) {
-        self.needs_update = false;
+    /// Chunk with a single block in its corner, bordering the -x, -y and -z neighbors
+    fn corner_chunk() -> ChunkSnapshot {
+        let mut chunk = Chunk::empty(ChunkPos::new(0, 0, 0));
+        chunk.set_block(LocalPos::new(0, 0, 0).unwrap(), Block::new(1));
+        chunk.snapshot()
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.block_data.is_none()
+    #[test]
+    fn unloaded_neighbors_follow_policy() {
+        let chunk = corner_chunk();
+        let neighbors = Default::default();
+
+        let (main, borders) = chunk.gen_mesh(&neighbors, UnloadedNeighbor::Air, &texture_info());
+        assert_eq!(main.0.len(), 3 * FACE_VERTICES);
+        let border_faces = borders.map(|border| border.unwrap().0.len() / FACE_VERTICES);
+        assert_eq!(border_faces, [0, 1, 1, 0, 0, 1]);
+
+        let (main, borders) = chunk.gen_mesh(&neighbors, UnloadedNeighbor::Solid, &texture_info());
+        assert_eq!(main.0.len(), 3 * FACE_VERTICES);
+        assert!(borders.iter().all(|border| border.as_ref().unwrap().0.is_empty()));
     }
 
-    pub fn needs_update(&self) -> bool {
-        self.needs_update
+    #[test]
+    fn loaded_neighbors_are_meshed_into_main_section() {
+        let chunk = corner_chunk();
+        let solid = Chunk::from_data(
+            ChunkPos::new(-1, 0, 0),
+            Array3::from_elem(CHUNK_SIZE, Block::new(2)),
+        );
+        let mut neighbors: [Option<ChunkSnapshot>; 6] = Default::default();
+        neighbors[1] = Some(solid.snapshot());
+        neighbors[2] = Some(Chunk::empty(ChunkPos::new(0, -1, 0)).snapshot());
+
+        let (main, borders) = chunk.gen_mesh(&neighbors, UnloadedNeighbor::Solid, &texture_info());
+        // The solid neighbor hides the -x face and the empty one shows the -y face
+        assert_eq!(main.0.len(), 4 * FACE_VERTICES);
+        assert!(borders[1].is_none() && borders[2].is_none() && borders[5].is_some());
+
+        // Rebuilding a border once its neighbor is loaded only builds the faces bordering it
+        let back = chunk.gen_border_mesh(5, None, UnloadedNeighbor::Air, &texture_info());
+        assert_eq!(back.0.len(), FACE_VERTICES);
+        let left = chunk.gen_border_mesh(1, neighbors[1].as_ref(), UnloadedNeighbor::Air, &texture_info());
+        assert!(left.0.is_empty());
     }
 
-    pub fn request_update(&mut self) {
-        self.needs_update = true;
+    #[test]
+    fn snapshots_are_not_changed_by_edits() {
+        let mut chunk = Chunk::empty(ChunkPos::new(0, 0, 0));
+        let pos = LocalPos::new(1, 2, 3).unwrap();
+        chunk.set_block(pos, Block::new(1));
+        let snapshot = chunk.snapshot();
+
+        chunk.set_block(pos, Block::new(2));
+        assert_eq!(snapshot.get_block(pos), Some(Block::new(1)));
+        assert_eq!(chunk.get_block(pos), Some(Block::new(2)));
+        assert_ne!(snapshot.version(), chunk.version());
+        assert_eq!(chunk.snapshot().version(), chunk.version());
     }
 }
-
-// impl Drop for Chunk {
-//     fn drop(&mut self) {
-//         if let Some(data) = &self.block_data {
-//             let serialized = bincode::serialize(&data);
-//             match serialized {
-//                 Ok(bytes) => {
-//                     save_to_file(bytes.as_slice(), &format!("chunks/x{}y{}z{}.chunk", self.coord.x, self.coord.y, self.coord.z));
-//                 },
-//                 Err(e) => {
-//                     println!("Error serializing chunk: {}", e);
-//                 }
-//             }
-//         }
-//     }
-// }
@@ -1,16 +1,60 @@
 use crate::chunk::*;
 
+/// Versions of a chunk and of the faces of its 6 neighbors touching it (in the order of `NeighborChunks`) a mesh was built from
+pub type MeshVersions = [u64; 7];
+
+/// Version of the face of a neighbor that wasn't loaded when a mesh was built. Chunk versions start at 1
+pub const UNLOADED_VERSION: u64 = 0;
+
+/// Vertex and index buffer of a section of a mesh
+pub type MeshBuffers = (glium::VertexBuffer<Vertex>, glium::IndexBuffer<u16>);
+
 pub struct ChunkMesh {
     mesh: glium::VertexBuffer<Vertex>,
     indices: glium::IndexBuffer<u16>,
+    /// Faces bordering the neighbors that weren't loaded when the mesh was built, replaced once they are loaded
+    borders: [Option<MeshBuffers>; 6],
+    versions: MeshVersions,
 }
 
 impl ChunkMesh {
-    pub fn new(mesh: glium::VertexBuffer<Vertex>, indices: glium::IndexBuffer<u16>) -> Self {
+    pub fn new(mesh: glium::VertexBuffer<Vertex>, indices: glium::IndexBuffer<u16>, versions: MeshVersions) -> Self {
         ChunkMesh {
             mesh,
             indices,
+            borders: Default::default(),
+            versions,
+        }
+    }
+
+    pub fn versions(&self) -> &MeshVersions {
+        &self.versions
+    }
+
+    /// Returns which neighbors weren't loaded when the faces bordering them were built
+    pub fn unloaded_neighbors(&self) -> [bool; 6] {
+        let mut unloaded = [false; 6];
+        for (unloaded, version) in unloaded.iter_mut().zip(&self.versions[1..]) {
+            *unloaded = *version == UNLOADED_VERSION;
         }
+        unloaded
+    }
+
+    /// Replaces the section with the faces bordering the neighbor at `face`, built from the face of the neighbor with `version`
+    pub fn set_border(&mut self, face: usize, buffers: Option<MeshBuffers>, version: u64) {
+        self.borders[face] = buffers;
+        self.versions[face + 1] = version;
+    }
+
+    /// Returns the number of bytes of GPU memory the mesh takes up
+    pub fn size(&self) -> usize {
+        self.sections().map(|(mesh, indices)| mesh.get_size() + indices.get_size()).sum()
+    }
+
+    /// Returns the buffers of every section of the mesh
+    pub fn sections(&self) -> impl Iterator<Item = (&glium::VertexBuffer<Vertex>, &glium::IndexBuffer<u16>)> {
+        std::iter::once((&self.mesh, &self.indices))
+            .chain(self.borders.iter().flatten().map(|(mesh, indices)| (mesh, indices)))
     }
 
     pub fn get_mesh(&self) -> &glium::VertexBuffer<Vertex> {
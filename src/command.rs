@@ -0,0 +1,23 @@
+use crate::daylight::{format_time_of_day, parse_time_of_day};
+use crate::world::World;
+
+/// Runs a command typed in the console, like `/time set noon`.
+/// Returns the message to show, or an error message if the command is invalid
+pub fn run(command: &str, world: &mut World) -> Result<String, String> {
+    let args: Vec<&str> = command.split_whitespace().collect();
+    match args.as_slice() {
+        ["/time"] | ["/time", "query"] => Ok(format!(
+            "Time is {}",
+            format_time_of_day(world.time_of_day())
+        )),
+        ["/time", "set", time] => {
+            let time_of_day =
+                parse_time_of_day(time).ok_or_else(|| format!("Invalid time: {}", time))?;
+            world.set_time_of_day(time_of_day);
+            Ok(format!("Set time to {}", format_time_of_day(time_of_day)))
+        }
+        ["/time", ..] => Err("Usage: /time [query | set <time>]".to_string()),
+        [] => Err("No command".to_string()),
+        [name, ..] => Err(format!("Unknown command: {}", name)),
+    }
+}
@@ -0,0 +1,425 @@
+use std::collections::HashMap;
+use std::fs::File;
+
+use serde::{Deserialize, Serialize};
+
+use crate::inventory::ItemStack;
+use crate::registry::ItemRegistry;
+
+/// Item and amount produced by a recipe
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecipeOutput {
+    pub item: u16,
+    #[serde(default = "default_output_count")]
+    pub count: u16,
+}
+
+fn default_output_count() -> u16 {
+    1
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Recipe {
+    /// Items must be laid out like `pattern`, where each character maps to an item id in `key`
+    /// and a space is an empty cell. The pattern may be placed anywhere in the grid
+    Shaped {
+        pattern: Vec<String>,
+        key: HashMap<String, u16>,
+        output: RecipeOutput,
+    },
+    /// Items may be placed anywhere in the grid
+    Shapeless {
+        ingredients: Vec<u16>,
+        output: RecipeOutput,
+    },
+    /// Turns `input` into `output` in a furnace after `duration` seconds
+    Smelting {
+        input: u16,
+        output: RecipeOutput,
+        duration: f32,
+    },
+}
+
+impl Recipe {
+    pub fn output(&self) -> &RecipeOutput {
+        match self {
+            Recipe::Shaped { output, .. } => output,
+            Recipe::Shapeless { output, .. } => output,
+            Recipe::Smelting { output, .. } => output,
+        }
+    }
+
+    /// Returns true if the items in `grid` satisfy this recipe
+    pub fn matches(&self, grid: &CraftingGrid) -> bool {
+        match self {
+            Recipe::Shaped { pattern, key, .. } => {
+                let (min, max) = match grid.bounds() {
+                    None => return false,
+                    Some(bounds) => bounds,
+                };
+                // Blank rows and columns around the pattern don't have to be in the grid
+                let rows: Vec<Vec<char>> = pattern.iter().map(|row| row.chars().collect()).collect();
+                let (pattern_min, pattern_max) = match pattern_bounds(&rows) {
+                    None => return false,
+                    Some(bounds) => bounds,
+                };
+                let width = pattern_max.0 - pattern_min.0 + 1;
+                let height = pattern_max.1 - pattern_min.1 + 1;
+                if max.0 - min.0 + 1 != width || max.1 - min.1 + 1 != height {
+                    return false;
+                }
+
+                for y in 0..height {
+                    for x in 0..width {
+                        let expected = match rows[pattern_min.1 + y].get(pattern_min.0 + x) {
+                            None | Some(' ') => None,
+                            Some(c) => match key.get(&c.to_string()) {
+                                None => return false,
+                                Some(id) => Some(*id),
+                            },
+                        };
+                        let actual = grid.get(min.0 + x, min.1 + y).map(|stack| stack.id());
+                        if expected != actual {
+                            return false;
+                        }
+                    }
+                }
+                true
+            }
+            Recipe::Shapeless { ingredients, .. } => {
+                let mut expected = ingredients.clone();
+                let mut actual: Vec<u16> = grid.slots.iter().flatten().map(|s| s.id()).collect();
+                expected.sort_unstable();
+                actual.sort_unstable();
+                expected == actual
+            }
+            Recipe::Smelting { .. } => false,
+        }
+    }
+}
+
+/// Returns the smallest (min, max) cells containing every non-blank character of a pattern
+fn pattern_bounds(rows: &[Vec<char>]) -> Option<((usize, usize), (usize, usize))> {
+    let mut bounds: Option<((usize, usize), (usize, usize))> = None;
+    for (y, row) in rows.iter().enumerate() {
+        for (x, c) in row.iter().enumerate() {
+            if *c != ' ' {
+                bounds = Some(match bounds {
+                    None => ((x, y), (x, y)),
+                    Some((min, max)) => {
+                        ((min.0.min(x), min.1.min(y)), (max.0.max(x), max.1.max(y)))
+                    }
+                });
+            }
+        }
+    }
+    bounds
+}
+
+#[derive(Serialize, Deserialize)]
+struct RecipeBookInfo {
+    recipes: Vec<Recipe>,
+}
+
+/// Holds every known recipe
+pub struct RecipeBook {
+    recipes: Vec<Recipe>,
+}
+
+impl RecipeBook {
+    /// Loads recipes from a json file. Recipes that use unknown items are skipped
+    pub fn load(path: &str, items: &ItemRegistry) -> RecipeBook {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                panic!("Error opening {}: {}", path, e);
+            }
+        };
+
+        let info: RecipeBookInfo = match serde_json::from_reader(file) {
+            Ok(v) => v,
+            Err(e) => {
+                panic!("Error parsing {}: {}", path, e);
+            }
+        };
+
+        let recipes = info
+            .recipes
+            .into_iter()
+            .filter(|recipe| {
+                let mut ids = vec![recipe.output().item];
+                match recipe {
+                    Recipe::Shaped { key, .. } => ids.extend(key.values()),
+                    Recipe::Shapeless { ingredients, .. } => ids.extend(ingredients),
+                    Recipe::Smelting { input, .. } => ids.push(*input),
+                }
+                match ids.iter().find(|id| items.get(**id).is_none()) {
+                    None => true,
+                    Some(id) => {
+                        println!("Skipping recipe using unknown item {}", id);
+                        false
+                    }
+                }
+            })
+            .collect();
+
+        RecipeBook::new(recipes)
+    }
+
+    pub fn new(recipes: Vec<Recipe>) -> RecipeBook {
+        RecipeBook { recipes }
+    }
+
+    /// Returns the first crafting recipe matching the items in `grid`
+    pub fn find(&self, grid: &CraftingGrid) -> Option<&Recipe> {
+        self.recipes.iter().find(|recipe| recipe.matches(grid))
+    }
+
+    /// Returns the smelting recipe for item `input`
+    pub fn find_smelting(&self, input: u16) -> Option<&Recipe> {
+        self.recipes.iter().find(|recipe| match recipe {
+            Recipe::Smelting { input: i, .. } => *i == input,
+            _ => false,
+        })
+    }
+}
+
+/// Square grid of item stacks used for crafting
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CraftingGrid {
+    size: usize,
+    slots: Vec<Option<ItemStack>>,
+}
+
+impl CraftingGrid {
+    pub fn new(size: usize) -> CraftingGrid {
+        CraftingGrid {
+            size,
+            slots: vec![None; size * size],
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&ItemStack> {
+        self.slots[y * self.size + x].as_ref()
+    }
+
+    /// Puts `stack` into cell (x, y), returning whatever was there before
+    pub fn set(&mut self, x: usize, y: usize, stack: Option<ItemStack>) -> Option<ItemStack> {
+        std::mem::replace(&mut self.slots[y * self.size + x], stack)
+    }
+
+    /// Empties the grid, returning all of its stacks
+    pub fn clear(&mut self) -> Vec<ItemStack> {
+        self.slots.iter_mut().filter_map(|slot| slot.take()).collect()
+    }
+
+    /// Returns the output of the recipe matching the grid
+    pub fn preview(&self, recipes: &RecipeBook, items: &ItemRegistry) -> Option<ItemStack> {
+        let output = recipes.find(self)?.output();
+        items.stack(output.item, output.count)
+    }
+
+    /// Crafts the recipe matching the grid, consuming one item from each occupied cell
+    pub fn craft(&mut self, recipes: &RecipeBook, items: &ItemRegistry) -> Option<ItemStack> {
+        let output = self.preview(recipes, items)?;
+        for slot in self.slots.iter_mut() {
+            if let Some(stack) = slot {
+                if stack.count() <= 1 {
+                    *slot = None;
+                } else {
+                    stack.shrink(1);
+                }
+            }
+        }
+        Some(output)
+    }
+
+    /// Returns the smallest (min, max) cells containing every occupied cell
+    fn bounds(&self) -> Option<((usize, usize), (usize, usize))> {
+        let mut bounds: Option<((usize, usize), (usize, usize))> = None;
+        for y in 0..self.size {
+            for x in 0..self.size {
+                if self.get(x, y).is_some() {
+                    bounds = Some(match bounds {
+                        None => ((x, y), (x, y)),
+                        Some((min, max)) => {
+                            ((min.0.min(x), min.1.min(y)), (max.0.max(x), max.1.max(y)))
+                        }
+                    });
+                }
+            }
+        }
+        bounds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::ItemInfo;
+
+    const DIRT: u16 = 2;
+    const STONE: u16 = 3;
+    const TABLE: u16 = 4;
+
+    fn items() -> ItemRegistry {
+        let mut items = HashMap::new();
+        for (id, name) in [(DIRT, "dirt"), (STONE, "stone"), (TABLE, "crafting_table")] {
+            items.insert(
+                id,
+                ItemInfo {
+                    name: name.to_string(),
+                    max_stack: 64,
+                    block: Some(id),
+                    tool: None,
+                    icon: None,
+                },
+            );
+        }
+        ItemRegistry::new(items)
+    }
+
+    fn stack(id: u16, count: u16) -> Option<ItemStack> {
+        Some(ItemStack::new(id, count, 64))
+    }
+
+    fn shaped() -> Recipe {
+        Recipe::Shaped {
+            pattern: vec!["dd".to_string(), "ss".to_string()],
+            key: HashMap::from([("d".to_string(), DIRT), ("s".to_string(), STONE)]),
+            output: RecipeOutput { item: TABLE, count: 1 },
+        }
+    }
+
+    #[test]
+    fn shaped_matches_anywhere_in_grid() {
+        let recipe = shaped();
+        for (ox, oy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let mut grid = CraftingGrid::new(3);
+            grid.set(ox, oy, stack(DIRT, 1));
+            grid.set(ox + 1, oy, stack(DIRT, 1));
+            grid.set(ox, oy + 1, stack(STONE, 1));
+            grid.set(ox + 1, oy + 1, stack(STONE, 1));
+            assert!(recipe.matches(&grid));
+        }
+    }
+
+    #[test]
+    fn shaped_rejects_wrong_layout() {
+        let recipe = shaped();
+        let mut grid = CraftingGrid::new(3);
+        grid.set(0, 0, stack(STONE, 1));
+        grid.set(1, 0, stack(STONE, 1));
+        grid.set(0, 1, stack(DIRT, 1));
+        grid.set(1, 1, stack(DIRT, 1));
+        assert!(!recipe.matches(&grid));
+
+        // Extra item outside of the pattern
+        let mut grid = CraftingGrid::new(3);
+        grid.set(0, 0, stack(DIRT, 1));
+        grid.set(1, 0, stack(DIRT, 1));
+        grid.set(0, 1, stack(STONE, 1));
+        grid.set(1, 1, stack(STONE, 1));
+        grid.set(2, 2, stack(STONE, 1));
+        assert!(!recipe.matches(&grid));
+
+        assert!(!recipe.matches(&CraftingGrid::new(3)));
+    }
+
+    #[test]
+    fn shaped_respects_gaps() {
+        let recipe = Recipe::Shaped {
+            pattern: vec!["d ".to_string(), " d".to_string()],
+            key: HashMap::from([("d".to_string(), DIRT)]),
+            output: RecipeOutput { item: STONE, count: 1 },
+        };
+        let mut grid = CraftingGrid::new(2);
+        grid.set(0, 0, stack(DIRT, 1));
+        grid.set(1, 1, stack(DIRT, 1));
+        assert!(recipe.matches(&grid));
+
+        grid.set(1, 0, stack(DIRT, 1));
+        assert!(!recipe.matches(&grid));
+    }
+
+    #[test]
+    fn shaped_ignores_blank_padding() {
+        let recipe = Recipe::Shaped {
+            pattern: vec!["   ".to_string(), " d ".to_string(), " s".to_string()],
+            key: HashMap::from([("d".to_string(), DIRT), ("s".to_string(), STONE)]),
+            output: RecipeOutput { item: TABLE, count: 1 },
+        };
+        for (x, y) in [(0, 0), (2, 1)] {
+            let mut grid = CraftingGrid::new(3);
+            grid.set(x, y, stack(DIRT, 1));
+            grid.set(x, y + 1, stack(STONE, 1));
+            assert!(recipe.matches(&grid));
+
+            grid.set(x, y + 1, stack(DIRT, 1));
+            assert!(!recipe.matches(&grid));
+        }
+
+        let blank = Recipe::Shaped {
+            pattern: vec![" ".to_string()],
+            key: HashMap::new(),
+            output: RecipeOutput { item: TABLE, count: 1 },
+        };
+        let mut grid = CraftingGrid::new(3);
+        grid.set(0, 0, stack(DIRT, 1));
+        assert!(!blank.matches(&grid));
+        assert!(!blank.matches(&CraftingGrid::new(3)));
+    }
+
+    #[test]
+    fn shapeless_ignores_position() {
+        let recipe = Recipe::Shapeless {
+            ingredients: vec![DIRT, STONE, DIRT],
+            output: RecipeOutput { item: TABLE, count: 1 },
+        };
+        let mut grid = CraftingGrid::new(3);
+        grid.set(2, 0, stack(DIRT, 1));
+        grid.set(0, 1, stack(STONE, 1));
+        grid.set(1, 2, stack(DIRT, 5));
+        assert!(recipe.matches(&grid));
+
+        grid.set(2, 2, stack(DIRT, 1));
+        assert!(!recipe.matches(&grid));
+    }
+
+    #[test]
+    fn craft_consumes_one_of_each_ingredient() {
+        let items = items();
+        let recipes = RecipeBook::new(vec![shaped()]);
+        let mut grid = CraftingGrid::new(2);
+        grid.set(0, 0, stack(DIRT, 2));
+        grid.set(1, 0, stack(DIRT, 1));
+        grid.set(0, 1, stack(STONE, 1));
+        grid.set(1, 1, stack(STONE, 1));
+
+        let output = grid.craft(&recipes, &items);
+        assert_eq!(output, stack(TABLE, 1));
+        assert_eq!(grid.get(0, 0), stack(DIRT, 1).as_ref());
+        assert_eq!(grid.get(1, 0), None);
+        assert_eq!(grid.craft(&recipes, &items), None);
+    }
+
+    #[test]
+    fn smelting_is_not_craftable() {
+        let items = items();
+        let recipes = RecipeBook::new(vec![Recipe::Smelting {
+            input: DIRT,
+            output: RecipeOutput { item: STONE, count: 1 },
+            duration: 10.0,
+        }]);
+        let mut grid = CraftingGrid::new(2);
+        grid.set(0, 0, stack(DIRT, 1));
+        assert_eq!(grid.craft(&recipes, &items), None);
+        assert!(recipes.find_smelting(DIRT).is_some());
+        assert!(recipes.find_smelting(STONE).is_none());
+    }
+}
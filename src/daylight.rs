@@ -0,0 +1,219 @@
+use std::f32::consts::TAU;
+
+/// Seconds of game time a day lasts
+pub const DAY_LENGTH: f64 = 1200.0;
+/// Time of day new worlds start at, shortly after sunrise
+pub const START_TIME_OF_DAY: f32 = 7.0 / 24.0;
+/// Sine of the sun's elevation below which its light fades out, so the light doesn't jump when the moon rises
+const HORIZON_FADE: f32 = 0.1;
+/// How far the path of the sun leans to the side, so the light never hits the ground straight down
+const SUN_TILT: f32 = 0.35;
+
+/// Sky and light at a time of day, between which the other times of day are interpolated
+#[derive(Clone, Copy, Debug)]
+struct Keyframe {
+    time_of_day: f32,
+    sky_color: [f32; 3],
+    fog_color: [f32; 3],
+    ambient: f32,
+    light_strength: f32,
+}
+
+const NIGHT: Keyframe = Keyframe {
+    time_of_day: 0.0,
+    sky_color: [0.02, 0.03, 0.08],
+    fog_color: [0.03, 0.04, 0.09],
+    ambient: 0.06,
+    light_strength: 0.2,
+};
+const DAWN: Keyframe = Keyframe {
+    time_of_day: 6.0 / 24.0,
+    sky_color: [0.85, 0.55, 0.45],
+    fog_color: [0.9, 0.65, 0.5],
+    ambient: 0.12,
+    light_strength: 0.7,
+};
+const DAY: Keyframe = Keyframe {
+    time_of_day: 7.5 / 24.0,
+    sky_color: [0.2, 0.6, 0.9],
+    fog_color: [0.45, 0.7, 0.92],
+    ambient: 0.15,
+    light_strength: 0.95,
+};
+const DUSK: Keyframe = Keyframe {
+    time_of_day: 18.0 / 24.0,
+    sky_color: [0.9, 0.45, 0.25],
+    fog_color: [0.85, 0.5, 0.3],
+    ambient: 0.12,
+    light_strength: 0.7,
+};
+
+/// Keyframes in order over a day, starting and ending at midnight
+const KEYFRAMES: [Keyframe; 7] = [
+    NIGHT,
+    Keyframe {
+        time_of_day: 5.0 / 24.0,
+        ..NIGHT
+    },
+    DAWN,
+    DAY,
+    Keyframe {
+        time_of_day: 16.5 / 24.0,
+        ..DAY
+    },
+    DUSK,
+    Keyframe {
+        time_of_day: 19.0 / 24.0,
+        ..NIGHT
+    },
+];
+
+/// Light and colours of the sky at a time of day, passed to the shaders
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lighting {
+    /// Direction the light of the sun, or of the moon at night, travels in
+    pub light: [f32; 3],
+    pub light_strength: f32,
+    /// Fraction of the light blocks get even when facing away from the sun
+    pub ambient: f32,
+    pub sky_color: [f32; 3],
+    /// Colour far away blocks fade into
+    pub fog_color: [f32; 3],
+}
+
+impl Lighting {
+    /// Returns the lighting at `time_of_day` (see `time_of_day`)
+    pub fn at(time_of_day: f32) -> Lighting {
+        let time_of_day = time_of_day.rem_euclid(1.0);
+        let next = KEYFRAMES
+            .iter()
+            .position(|key| key.time_of_day > time_of_day);
+        let (from, to, to_time) = match next {
+            Some(i) => (KEYFRAMES[i - 1], KEYFRAMES[i], KEYFRAMES[i].time_of_day),
+            None => (KEYFRAMES[KEYFRAMES.len() - 1], KEYFRAMES[0], 1.0),
+        };
+        let t = (time_of_day - from.time_of_day) / (to_time - from.time_of_day);
+
+        let sun = sun_direction(time_of_day);
+        // The moon is opposite of the sun
+        let light = if sun[1] >= 0.0 { sun.map(|s| -s) } else { sun };
+        let visible = (sun[1].abs() / HORIZON_FADE).min(1.0);
+        Lighting {
+            light,
+            light_strength: lerp(from.light_strength, to.light_strength, t) * visible,
+            ambient: lerp(from.ambient, to.ambient, t),
+            sky_color: lerp_color(from.sky_color, to.sky_color, t),
+            fog_color: lerp_color(from.fog_color, to.fog_color, t),
+        }
+    }
+}
+
+/// Returns the time of day of a world time in seconds, from 0 at midnight to 0.5 at noon and back to 1
+pub fn time_of_day(time: f64) -> f32 {
+    (time / DAY_LENGTH).rem_euclid(1.0) as f32
+}
+
+/// Returns the direction from the ground to the sun, normalized. The sun rises in +x at 6:00, is highest
+/// at noon and sets in -x at 18:00
+pub fn sun_direction(time_of_day: f32) -> [f32; 3] {
+    let angle = (time_of_day - 0.25) * TAU;
+    let direction = [angle.cos(), angle.sin(), SUN_TILT];
+    let length = direction.iter().map(|d| d * d).sum::<f32>().sqrt();
+    direction.map(|d| d / length)
+}
+
+/// Parses a time of day: `sunrise`, `day`, `noon`, `sunset`, `night`, `midnight`, hours like `13.5` or a
+/// clock time like `13:30`. Returns none if it's invalid
+pub fn parse_time_of_day(text: &str) -> Option<f32> {
+    let hours = match text {
+        "sunrise" => 6.0,
+        "day" => 7.0,
+        "noon" => 12.0,
+        "sunset" => 18.0,
+        "night" => 19.0,
+        "midnight" => 0.0,
+        text => match text.split_once(':') {
+            Some((hours, minutes)) => {
+                let minutes: u32 = minutes.parse().ok().filter(|minutes| *minutes < 60)?;
+                hours.parse::<u32>().ok()? as f32 + minutes as f32 / 60.0
+            }
+            None => text.parse().ok().filter(|hours: &f32| hours.is_finite())?,
+        },
+    };
+    (0.0..=24.0).contains(&hours).then(|| hours / 24.0 % 1.0)
+}
+
+/// Formats a time of day as a 24-hour clock time like `13:30`
+pub fn format_time_of_day(time_of_day: f32) -> String {
+    let minutes = (time_of_day.rem_euclid(1.0) * 24.0 * 60.0) as u32 % (24 * 60);
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+fn lerp(from: f32, to: f32, t: f32) -> f32 {
+    from + (to - from) * t
+}
+
+fn lerp_color(from: [f32; 3], to: [f32; 3], t: f32) -> [f32; 3] {
+    [0, 1, 2].map(|i| lerp(from[i], to[i], t))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sun_moves_across_sky() {
+        let noon = sun_direction(0.5);
+        assert!(noon[1] > 0.9);
+        assert!(sun_direction(0.0)[1] < -0.9);
+        assert!(sun_direction(0.25)[0] > 0.9 && sun_direction(0.75)[0] < -0.9);
+
+        // Light comes from above at day and at night
+        assert!(Lighting::at(0.5).light[1] < 0.0);
+        assert!(Lighting::at(0.0).light[1] < 0.0);
+        // and fades out when the sun sets
+        assert!(Lighting::at(0.75).light_strength < 0.01);
+        assert!(Lighting::at(0.5).light_strength > Lighting::at(0.0).light_strength);
+    }
+
+    #[test]
+    fn colors_follow_time_of_day() {
+        assert_eq!(Lighting::at(0.5).sky_color, DAY.sky_color);
+        assert_eq!(Lighting::at(DUSK.time_of_day).sky_color, DUSK.sky_color);
+        assert_eq!(Lighting::at(0.0).sky_color, NIGHT.sky_color);
+        assert!(Lighting::at(0.5).ambient > Lighting::at(0.0).ambient);
+
+        // Colours change smoothly, also from one day to the next
+        for i in 0..1000 {
+            let (a, b) = (
+                Lighting::at(i as f32 / 1000.0),
+                Lighting::at((i + 1) as f32 / 1000.0),
+            );
+            for c in 0..3 {
+                assert!(
+                    (a.sky_color[c] - b.sky_color[c]).abs() < 0.05,
+                    "{:?} {:?}",
+                    a,
+                    b
+                );
+            }
+        }
+        assert_eq!(time_of_day(DAY_LENGTH * 2.5), 0.5);
+        assert_eq!(time_of_day(-DAY_LENGTH * 0.25), 0.75);
+    }
+
+    #[test]
+    fn times_of_day_are_parsed() {
+        assert_eq!(parse_time_of_day("noon"), Some(0.5));
+        assert_eq!(parse_time_of_day("18"), Some(0.75));
+        assert_eq!(parse_time_of_day("24"), Some(0.0));
+        assert_eq!(parse_time_of_day("6:00"), Some(0.25));
+        assert_eq!(
+            format_time_of_day(parse_time_of_day("13:30").unwrap()),
+            "13:30"
+        );
+        for invalid in ["25", "-1", "dusk", "12:60", "NaN", ""] {
+            assert_eq!(parse_time_of_day(invalid), None, "{}", invalid);
+        }
+    }
+}
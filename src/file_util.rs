@@ -4,56 +4,45 @@ use std::io::prelude::*;
 use miniz_oxide::deflate::compress_to_vec;
 use miniz_oxide::inflate::decompress_to_vec;
 
-use crate::chunk::Block;
-
+/// Compresses `data` and writes it to `path`, creating its directory. Returns false if it couldn't be written
 ///
 /// fn main() {
 ///     save_to_file(b"I'm bill", "bill.txt");
 /// }
-pub fn save_to_file(data: &[u8], path: &str) {
+pub fn save_to_file(data: &[u8], path: &str) -> bool {
     //println!("Uncompressed size: {} bytes", data.len());
     let data = compress_to_vec(data, 8);
     //println!("Compressed size: {} bytes", data.as_slice().len());
 
-    let f = OpenOptions::new().write(true).create(true).open(path);
-
-    match f {
-        Ok(mut f) => {
-            f.write_all(data.as_slice()).expect("Unable to write data");
+    if let Some(parent) = std::path::Path::new(path).parent() {
+        if let Err(e) = std::fs::create_dir_all(parent) {
+            println!("Error creating directory {}: {e}", parent.display());
         }
+    }
+
+    let f = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path);
+
+    match f.and_then(|mut f| f.write_all(data.as_slice())) {
+        Ok(_) => true,
         Err(e) => {
-            println!("Error saving chunk: {e}");
+            println!("Error saving {path}: {e}");
+            false
         }
     }
 }
 
-///
-/// fn main() {
-///     match read_from_file("bill.txt") {
-///         Ok(s) => println!("{}", s),
-///         Err(e) => println!("Failed to read file: {:?}", e),
-///     }
-///  }
-pub fn read_chunk_data_from_file(path: &str) -> Option<Box<ndarray::Array3<Block>>> {
+/// Reads and decompresses a file written with `save_to_file`. Returns none if the file
+/// doesn't exist or can't be decompressed
+pub fn read_from_file(path: &str) -> Option<Vec<u8>> {
     if let Ok(mut f) = File::open(path) {
         let mut data = Vec::new();
         f.read_to_end(&mut data).expect("Unable to read data");
 
-        let decompressed = decompress_to_vec(data.as_slice());
-
-        match decompressed {
-            Ok(decompressed) => {
-                let serialized = bincode::deserialize(decompressed.as_slice());
-                match serialized {
-                    Err(e) => {
-                        println!("Error reading chunk: {e}");
-                        None
-                    }
-                    Ok(chunk_data) => Some(Box::new(chunk_data)),
-                }
-            }
-            Err(_) => None,
-        }
+        decompress_to_vec(data.as_slice()).ok()
     } else {
         None
     }
@@ -1,13 +1,29 @@
 use std::collections::HashMap;
 
-use glium::glutin::event::{ElementState, MouseButton, VirtualKeyCode};
+use glium::glutin::event::{ElementState, MouseButton, MouseScrollDelta, VirtualKeyCode};
+use serde::{Deserialize, Serialize};
+
+use crate::actions::Button;
+
+/// Pixels of touchpad scrolling counted as one line of a mouse wheel
+const PIXELS_PER_LINE: f64 = 40.0;
 
 /// Keeps track of which keys have been pressed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Input {
     state: HashMap<VirtualKeyCode, ElementState>,
     mouse_button_state: HashMap<MouseButton, ElementState>,
     mouse_delta: (f64, f64),
+    /// Lines scrolled up since the last reset, negative for down
+    scroll: f32,
+}
+
+impl Default for Input {
+    fn default() -> Input {
+        Input::new()
+    }
 }
+
 impl Input {
     /// Constructs a new KeyboardState with all the keys released.
     pub fn new() -> Input {
@@ -15,6 +31,7 @@ impl Input {
             state: HashMap::new(),
             mouse_button_state: HashMap::new(),
             mouse_delta: (0.0, 0.0),
+            scroll: 0.0,
         }
     }
 
@@ -58,12 +75,22 @@ impl Input {
         !self.is_mouse_button_pressed(button)
     }
 
+    /// Returns true if the key or mouse button is pressed
+    pub fn is_button_pressed(&self, button: &Button) -> bool {
+        match button {
+            Button::Key(key) => self.is_key_pressed(key),
+            Button::Mouse(button) => self.is_mouse_button_pressed(button),
+        }
+    }
+
     pub fn update_mouse_button(&mut self, button: MouseButton, state: ElementState) {
         self.mouse_button_state.insert(button, state);
     }
 
+    /// Adds the motion of a mouse event. Every event between two ticks counts
     pub fn update_mouse_motion(&mut self, delta: (f64, f64)) {
-        self.mouse_delta = delta;
+        self.mouse_delta.0 += delta.0;
+        self.mouse_delta.1 += delta.1;
     }
 
     pub fn get_mouse_delta_x(&self) -> f64 {
@@ -73,4 +100,39 @@ impl Input {
     pub fn get_mouse_delta_y(&self) -> f64 {
         self.mouse_delta.1
     }
+
+    /// Adds the vertical scrolling of a mouse wheel event
+    pub fn update_scroll(&mut self, delta: MouseScrollDelta) {
+        self.scroll += match delta {
+            MouseScrollDelta::LineDelta(_, y) => y,
+            MouseScrollDelta::PixelDelta(position) => (position.y / PIXELS_PER_LINE) as f32,
+        };
+    }
+
+    /// Returns the lines scrolled up since the last reset, negative for down
+    pub fn get_scroll(&self) -> f32 {
+        self.scroll
+    }
+
+    /// Resets the mouse motion and scrolling, to be called once they are used by a tick
+    pub fn reset_deltas(&mut self) {
+        self.mouse_delta = (0.0, 0.0);
+        self.scroll = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mouse_motion_between_ticks_adds_up() {
+        let mut input = Input::new();
+        for _ in 0..4 {
+            input.update_mouse_motion((1.5, -2.0));
+        }
+        assert_eq!((input.get_mouse_delta_x(), input.get_mouse_delta_y()), (6.0, -8.0));
+        input.reset_deltas();
+        assert_eq!((input.get_mouse_delta_x(), input.get_mouse_delta_y()), (0.0, 0.0));
+    }
 }
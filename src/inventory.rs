@@ -1,20 +1,377 @@
-use crate::chunk::Block;
+use serde::{Deserialize, Serialize};
 
+use crate::file_util::{read_from_file, save_to_file};
+
+/// Number of slots at the start of the inventory that make up the hotbar
+pub const HOTBAR_SIZE: usize = 9;
+
+/// A number of items of the same type occupying a single slot
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ItemStack {
+    id: u16,
+    count: u16,
+    max_stack: u16,
+    /// Remaining uses for tools
+    durability: Option<u16>,
+}
+
+impl ItemStack {
+    pub fn new(id: u16, count: u16, max_stack: u16) -> ItemStack {
+        ItemStack {
+            id,
+            count,
+            max_stack,
+            durability: None,
+        }
+    }
+
+    pub fn with_durability(mut self, durability: u16) -> ItemStack {
+        self.durability = Some(durability);
+        self
+    }
+
+    pub fn durability(&self) -> Option<u16> {
+        self.durability
+    }
+
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    pub fn count(&self) -> u16 {
+        self.count
+    }
+
+    pub fn max_stack(&self) -> u16 {
+        self.max_stack
+    }
+
+    /// Removes up to `amount` items from the stack
+    pub fn shrink(&mut self, amount: u16) {
+        self.count = self.count.saturating_sub(amount);
+    }
+
+    /// Moves as many items as fit from `other` into this stack. Returns the number of items moved
+    pub fn merge(&mut self, other: &mut ItemStack) -> u16 {
+        if self.id != other.id || self.durability != other.durability {
+            return 0;
+        }
+        let moved = other.count.min(self.max_stack.saturating_sub(self.count));
+        self.count += moved;
+        other.count -= moved;
+        moved
+    }
+}
+
+/// Slot based inventory. The first `HOTBAR_SIZE` slots make up the hotbar
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Inventory {
-    num_slots: u32,
-    data: Vec<Box<dyn Item>>
+    slots: Vec<Option<ItemStack>>,
+    selected: usize,
 }
 
 impl Inventory {
-    pub fn new(num_slots: u32) -> Self {
-        Self { num_slots, data: Vec::new() }
+    pub fn new(num_slots: usize) -> Self {
+        Self {
+            slots: vec![None; num_slots],
+            selected: 0,
+        }
+    }
+
+    pub fn num_slots(&self) -> usize {
+        self.slots.len()
     }
-}
 
-pub trait Item {
+    pub fn get(&self, slot: usize) -> Option<&ItemStack> {
+        self.slots.get(slot).and_then(|s| s.as_ref())
+    }
+
+    /// Adds a stack to the inventory, filling existing stacks of the same item first.
+    /// Returns the items that didn't fit
+    pub fn add(&mut self, mut stack: ItemStack) -> Option<ItemStack> {
+        for existing in self.slots.iter_mut().flatten() {
+            if stack.count == 0 {
+                return None;
+            }
+            existing.merge(&mut stack);
+        }
+
+        for slot in self.slots.iter_mut() {
+            if stack.count == 0 {
+                return None;
+            }
+            if slot.is_none() {
+                let count = stack.count.min(stack.max_stack);
+                *slot = Some(ItemStack {
+                    count,
+                    ..stack.clone()
+                });
+                stack.count -= count;
+            }
+        }
+
+        if stack.count == 0 {
+            None
+        } else {
+            Some(stack)
+        }
+    }
 
+    /// Returns true if the whole stack fits into the inventory
+    pub fn can_add(&self, stack: &ItemStack) -> bool {
+        let room: u32 = self
+            .slots
+            .iter()
+            .map(|slot| match slot {
+                None => stack.max_stack as u32,
+                Some(existing) if existing.id == stack.id && existing.durability == stack.durability => {
+                    existing.max_stack.saturating_sub(existing.count) as u32
+                }
+                Some(_) => 0,
+            })
+            .sum();
+        room >= stack.count as u32
+    }
+
+    /// Removes up to `count` items from `slot`. Returns the removed items
+    pub fn remove(&mut self, slot: usize, count: u16) -> Option<ItemStack> {
+        let stack = self.slots.get_mut(slot)?.as_mut()?;
+        let count = count.min(stack.count);
+        stack.count -= count;
+        let removed = ItemStack {
+            count,
+            ..stack.clone()
+        };
+        if stack.count == 0 {
+            self.slots[slot] = None;
+        }
+        if count == 0 {
+            None
+        } else {
+            Some(removed)
+        }
+    }
+
+    /// Removes the whole stack in `slot`
+    pub fn take(&mut self, slot: usize) -> Option<ItemStack> {
+        self.slots.get_mut(slot)?.take()
+    }
+
+    /// Moves half of the stack in `slot` (rounded down) into the first empty slot.
+    /// Returns false if the stack can't be split or there's no empty slot
+    pub fn split(&mut self, slot: usize) -> bool {
+        let half = match self.get(slot) {
+            Some(stack) if stack.count > 1 => stack.count / 2,
+            _ => return false,
+        };
+        let empty = match self.slots.iter().position(|s| s.is_none()) {
+            Some(empty) => empty,
+            None => return false,
+        };
+        let removed = self.remove(slot, half);
+        self.slots[empty] = removed;
+        true
+    }
+
+    /// Moves as many items as fit from slot `from` into slot `to`.
+    /// If `to` is empty, the whole stack is moved. Returns false if either slot doesn't exist
+    pub fn merge(&mut self, from: usize, to: usize) -> bool {
+        if from >= self.slots.len() || to >= self.slots.len() {
+            return false;
+        }
+        if from == to {
+            return true;
+        }
+        let mut source = match self.take(from) {
+            Some(source) => source,
+            None => return true,
+        };
+        match &mut self.slots[to] {
+            None => {
+                self.slots[to] = Some(source);
+                return true;
+            }
+            Some(target) => {
+                target.merge(&mut source);
+            }
+        }
+        if source.count > 0 {
+            self.slots[from] = Some(source);
+        }
+        true
+    }
+
+    /// Swaps the stacks in two slots. Returns false if either slot doesn't exist
+    pub fn swap(&mut self, a: usize, b: usize) -> bool {
+        if a >= self.slots.len() || b >= self.slots.len() {
+            return false;
+        }
+        self.slots.swap(a, b);
+        true
+    }
+
+    /// Selects hotbar slot `slot`
+    pub fn select(&mut self, slot: usize) {
+        if slot < HOTBAR_SIZE.min(self.slots.len()) {
+            self.selected = slot;
+        }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn selected_stack(&self) -> Option<&ItemStack> {
+        self.get(self.selected)
+    }
+
+    /// Uses up `amount` durability of the selected tool, removing it when it breaks
+    pub fn damage_selected(&mut self, amount: u16) {
+        let slot = match self.slots.get_mut(self.selected) {
+            Some(slot) => slot,
+            None => return,
+        };
+        if let Some(stack) = slot {
+            if let Some(durability) = stack.durability {
+                if durability <= amount {
+                    *slot = None;
+                } else {
+                    stack.durability = Some(durability - amount);
+                }
+            }
+        }
+    }
+
+    pub fn save(&self, path: &str) {
+        match bincode::serialize(self) {
+            Ok(bytes) => {
+                save_to_file(bytes.as_slice(), path);
+            }
+            Err(e) => println!("Error serializing inventory: {}", e),
+        }
+    }
+
+    /// Loads an inventory from file. Returns none if the file doesn't exist or is invalid
+    pub fn load(path: &str) -> Option<Inventory> {
+        let data = read_from_file(path)?;
+        match bincode::deserialize(data.as_slice()) {
+            Ok(inventory) => Some(inventory),
+            Err(e) => {
+                println!("Error reading inventory: {}", e);
+                None
+            }
+        }
+    }
 }
 
-impl Item for Block {
+impl Default for Inventory {
+    fn default() -> Self {
+        Inventory::new(4 * HOTBAR_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stone(count: u16) -> ItemStack {
+        ItemStack::new(1, count, 64)
+    }
 
-}
\ No newline at end of file
+    #[test]
+    fn add_fills_existing_stacks_first() {
+        let mut inventory = Inventory::new(3);
+        assert_eq!(inventory.add(stone(60)), None);
+        inventory.add(ItemStack::new(2, 1, 1));
+        assert_eq!(inventory.add(stone(10)), None);
+        assert_eq!(inventory.get(0), Some(&stone(64)));
+        assert_eq!(inventory.get(1), Some(&ItemStack::new(2, 1, 1)));
+        assert_eq!(inventory.get(2), Some(&stone(6)));
+
+        // Only what fits is added
+        assert!(inventory.can_add(&stone(58)));
+        assert!(!inventory.can_add(&stone(59)));
+        assert_eq!(inventory.add(stone(60)), Some(stone(2)));
+        assert_eq!(inventory.get(2), Some(&stone(64)));
+    }
+
+    #[test]
+    fn remove_and_split_stacks() {
+        let mut inventory = Inventory::new(3);
+        inventory.add(stone(5));
+        assert_eq!(inventory.remove(0, 2), Some(stone(2)));
+        assert_eq!(inventory.get(0), Some(&stone(3)));
+        assert_eq!(inventory.remove(0, 10), Some(stone(3)));
+        assert_eq!(inventory.get(0), None);
+        assert_eq!(inventory.remove(0, 1), None);
+        assert_eq!(inventory.remove(7, 1), None);
+
+        inventory.add(stone(5));
+        assert!(inventory.split(0));
+        assert_eq!(inventory.get(0), Some(&stone(3)));
+        assert_eq!(inventory.get(1), Some(&stone(2)));
+        inventory.remove(1, 1);
+        assert!(!inventory.split(1));
+        assert!(!inventory.split(2));
+        assert!(!inventory.split(7));
+    }
+
+    #[test]
+    fn merge_and_swap_slots() {
+        let mut inventory = Inventory::new(3);
+        inventory.add(stone(64));
+        inventory.add(stone(10));
+        inventory.add(ItemStack::new(2, 1, 1));
+        inventory.remove(0, 60);
+
+        // Merging moves what fits and leaves the rest
+        assert!(inventory.merge(1, 0));
+        assert_eq!(inventory.get(0), Some(&stone(14)));
+        assert_eq!(inventory.get(1), None);
+        // Adding fills slot 0 up to 64, leaving 10 in slot 1, then all but 10 fit into slot 1
+        inventory.add(stone(60));
+        assert!(inventory.merge(0, 1));
+        assert_eq!(inventory.get(0), Some(&stone(10)));
+        assert_eq!(inventory.get(1), Some(&stone(64)));
+
+        // Different items don't merge
+        assert!(inventory.merge(2, 0));
+        assert_eq!(inventory.get(2), Some(&ItemStack::new(2, 1, 1)));
+
+        assert!(inventory.swap(0, 2));
+        assert_eq!(inventory.get(0), Some(&ItemStack::new(2, 1, 1)));
+        assert_eq!(inventory.get(2), Some(&stone(10)));
+    }
+
+    #[test]
+    fn tools_break_when_used_up() {
+        let mut inventory = Inventory::new(2);
+        inventory.add(ItemStack::new(5, 1, 1).with_durability(3));
+        inventory.add(stone(5));
+
+        inventory.damage_selected(2);
+        assert_eq!(inventory.selected_stack().and_then(|stack| stack.durability()), Some(1));
+        inventory.damage_selected(1);
+        assert_eq!(inventory.selected_stack(), None);
+
+        // Items without durability aren't damaged
+        inventory.select(1);
+        inventory.damage_selected(10);
+        assert_eq!(inventory.selected_stack(), Some(&stone(5)));
+    }
+
+    #[test]
+    fn slots_out_of_range_are_ignored() {
+        let mut inventory = Inventory::new(2);
+        inventory.add(stone(5));
+        assert!(!inventory.merge(0, 2));
+        assert!(!inventory.merge(2, 0));
+        assert!(!inventory.swap(0, 2));
+        assert_eq!(inventory.get(0), Some(&stone(5)));
+        assert_eq!(inventory.take(2), None);
+
+        let mut empty = Inventory::new(0);
+        empty.damage_selected(1);
+        assert_eq!(empty.selected_stack(), None);
+    }
+}
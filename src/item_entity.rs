@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use parry3d::bounding_volume::BoundingVolume;
+use parry3d::bounding_volume::AABB;
+use parry3d::na::Point3;
+use serde::{Deserialize, Serialize};
+
+use crate::chunk::Faces;
+use crate::inventory::ItemStack;
+use crate::pos::BlockPos;
+use crate::registry::ItemRegistry;
+
+/// Half of the side length of a dropped item
+pub const ITEM_HALF_SIZE: f32 = 0.125;
+/// Seconds after being dropped before an item can be picked up
+const PICKUP_DELAY: f32 = 0.5;
+/// Distance from the player's bounding box at which items are picked up
+pub const PICKUP_RADIUS: f32 = 0.75;
+/// Distance at which identical items merge into one stack
+const MERGE_RADIUS: f32 = 0.75;
+const GRAVITY: f32 = 20.0;
+/// Fraction of horizontal velocity kept per second while on the ground
+const GROUND_FRICTION: f32 = 0.02;
+/// Horizontal speed below which items on the ground stop, so resting items don't keep creeping
+const REST_SPEED: f32 = 0.01;
+
+/// An item stack lying in the world
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ItemEntity {
+    pub position: [f32; 3],
+    pub velocity: [f32; 3],
+    pub stack: ItemStack,
+    /// Seconds since the item was dropped
+    pub age: f32,
+}
+
+impl ItemEntity {
+    pub fn new(position: [f32; 3], velocity: [f32; 3], stack: ItemStack) -> ItemEntity {
+        ItemEntity {
+            position,
+            velocity,
+            stack,
+            age: 0.0,
+        }
+    }
+
+    /// Returns the coordinate of the block the item is in
+    pub fn block_coord(&self) -> BlockPos {
+        BlockPos::containing(self.position)
+    }
+
+    pub fn aabb(&self) -> AABB {
+        aabb_at(self.position)
+    }
+
+    pub fn can_be_picked_up(&self) -> bool {
+        self.age >= PICKUP_DELAY
+    }
+
+    /// Applies gravity and moves the item one axis at a time, stopping at blocks for which `is_solid` is true
+    pub fn step(&mut self, delta: f32, is_solid: impl Fn(BlockPos) -> bool) {
+        self.age += delta;
+        self.velocity[1] -= GRAVITY * delta;
+
+        let mut on_ground = false;
+        for axis in 0..3 {
+            let mut next = self.position;
+            next[axis] += self.velocity[axis] * delta;
+            if collides(aabb_at(next), &is_solid) {
+                if axis == 1 && self.velocity[1] < 0.0 {
+                    on_ground = true;
+                }
+                self.velocity[axis] = 0.0;
+            } else {
+                self.position = next;
+            }
+        }
+
+        if on_ground {
+            let friction = GROUND_FRICTION.powf(delta);
+            self.velocity[0] *= friction;
+            self.velocity[2] *= friction;
+            if self.velocity[0].hypot(self.velocity[2]) < REST_SPEED {
+                self.velocity[0] = 0.0;
+                self.velocity[2] = 0.0;
+            }
+        }
+    }
+}
+
+fn aabb_at([x, y, z]: [f32; 3]) -> AABB {
+    AABB::new(
+        Point3::new(x - ITEM_HALF_SIZE, y - ITEM_HALF_SIZE, z - ITEM_HALF_SIZE),
+        Point3::new(x + ITEM_HALF_SIZE, y + ITEM_HALF_SIZE, z + ITEM_HALF_SIZE),
+    )
+}
+
+fn collides(aabb: AABB, is_solid: &impl Fn(BlockPos) -> bool) -> bool {
+    for x in aabb.mins.x.floor() as i32..=aabb.maxs.x.floor() as i32 {
+        for y in aabb.mins.y.floor() as i32..=aabb.maxs.y.floor() as i32 {
+            for z in aabb.mins.z.floor() as i32..=aabb.maxs.z.floor() as i32 {
+                if is_solid(BlockPos::new(x, y, z)) {
+                    let block_aabb = AABB::new(
+                        Point3::new(x as f32, y as f32, z as f32),
+                        Point3::new((x + 1) as f32, (y + 1) as f32, (z + 1) as f32),
+                    );
+                    if aabb.intersects(&block_aabb) {
+                        return true;
+                    }
+                }
+            }
+        }
+    }
+    false
+}
+
+/// Merges nearby stacks of the same item. Emptied items are removed
+pub fn merge_items(items: &mut Vec<ItemEntity>) {
+    for i in 0..items.len() {
+        for j in (i + 1)..items.len() {
+            let (a, b) = items.split_at_mut(j);
+            let (a, b) = (&mut a[i], &mut b[0]);
+            if a.stack.count() == 0 || b.stack.count() == 0 {
+                continue;
+            }
+            let distance_squared: f32 = (0..3)
+                .map(|axis| (a.position[axis] - b.position[axis]).powi(2))
+                .sum();
+            if distance_squared <= MERGE_RADIUS * MERGE_RADIUS {
+                a.stack.merge(&mut b.stack);
+                a.age = a.age.min(b.age);
+            }
+        }
+    }
+    items.retain(|item| item.stack.count() > 0);
+}
+
+/// Small xorshift generator used to scatter dropped items
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed.max(1))
+    }
+
+    /// Returns a number in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ItemVertex {
+    position: [f32; 3],
+    normal: [f32; 3],
+    tex_coords: [f32; 2],
+}
+
+implement_vertex!(ItemVertex, position, normal, tex_coords);
+
+/// Builds a mesh of small textured cubes for dropped items, with positions relative to `origin`
+pub fn gen_item_mesh<'a>(
+    items: impl Iterator<Item = &'a ItemEntity>,
+    origin: BlockPos,
+    registry: &ItemRegistry,
+    texture_map_info: &Arc<HashMap<u16, [[[f32; 2]; 4]; 6]>>,
+) -> (Vec<ItemVertex>, Vec<u32>) {
+    const FACE_INDICES: &[u32; 6] = &[2, 1, 0, 0, 3, 2];
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+
+    for item in items {
+        let texture = registry
+            .get(item.stack.id())
+            .and_then(|info| info.block.or(info.icon))
+            .and_then(|block| texture_map_info.get(&block));
+        let texture = match texture {
+            None => continue,
+            Some(texture) => texture,
+        };
+
+        let position = [
+            (item.position[0] as f64 - origin.x as f64) as f32,
+            (item.position[1] as f64 - origin.y as f64) as f32,
+            (item.position[2] as f64 - origin.z as f64) as f32,
+        ];
+        for face in Faces::ALL {
+            let start = vertices.len() as u32;
+            for (c, (fx, fy, fz)) in face.points.iter().enumerate() {
+                vertices.push(ItemVertex {
+                    position: [
+                        position[0] + (*fx as f32 * 2.0 - 1.0) * ITEM_HALF_SIZE,
+                        position[1] + (*fy as f32 * 2.0 - 1.0) * ITEM_HALF_SIZE,
+                        position[2] + (*fz as f32 * 2.0 - 1.0) * ITEM_HALF_SIZE,
+                    ],
+                    normal: [
+                        face.normal.0 as f32,
+                        face.normal.1 as f32,
+                        face.normal.2 as f32,
+                    ],
+                    tex_coords: texture[face.face_id as usize][c],
+                });
+            }
+            indices.extend(FACE_INDICES.iter().map(|i| start + i));
+        }
+    }
+
+    (vertices, indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DELTA: f32 = 1.0 / 60.0;
+
+    fn item(position: [f32; 3], id: u16, count: u16) -> ItemEntity {
+        ItemEntity::new(position, [0.0; 3], ItemStack::new(id, count, 64))
+    }
+
+    #[test]
+    fn items_settle_on_the_ground() {
+        let ground = |pos: BlockPos| pos.y < 0;
+        let mut item = ItemEntity::new([0.5, 3.0, 0.5], [2.0, 0.0, -1.0], ItemStack::new(1, 1, 64));
+        for _ in 0..600 {
+            item.step(DELTA, ground);
+        }
+        assert!(
+            (item.position[1] - ITEM_HALF_SIZE).abs() < 0.05,
+            "{:?}",
+            item.position
+        );
+        assert_eq!([item.velocity[0], item.velocity[2]], [0.0, 0.0]);
+        assert!(item.position[0] > 0.5 && item.position[2] < 0.5);
+        assert!(item.can_be_picked_up());
+
+        // Resting items don't move anymore
+        let position = item.position;
+        item.step(DELTA, ground);
+        assert_eq!(item.position, position);
+
+        // and walls stop them
+        let wall = |pos: BlockPos| pos.y < 0 || pos.x >= 2;
+        let mut item = ItemEntity::new(
+            [1.5, ITEM_HALF_SIZE, 0.5],
+            [10.0, 0.0, 0.0],
+            ItemStack::new(1, 1, 64),
+        );
+        for _ in 0..60 {
+            item.step(DELTA, wall);
+        }
+        assert!(item.position[0] <= 2.0 - ITEM_HALF_SIZE);
+    }
+
+    #[test]
+    fn nearby_stacks_merge() {
+        let mut items = vec![
+            item([0.0, 0.0, 0.0], 1, 10),
+            item([0.5, 0.0, 0.0], 1, 60),
+            item([0.0, 0.5, 0.0], 2, 1),
+            item([5.0, 0.0, 0.0], 1, 3),
+        ];
+        items[0].age = 2.0;
+        merge_items(&mut items);
+
+        // The first stack fills up, the rest stays in the second one
+        let counts: Vec<(u16, u16)> = items
+            .iter()
+            .map(|item| (item.stack.id(), item.stack.count()))
+            .collect();
+        assert_eq!(counts, [(1, 64), (1, 6), (2, 1), (1, 3)]);
+        assert_eq!(items[0].age, 0.0);
+
+        // Emptied stacks are removed
+        let mut items = vec![item([0.0, 0.0, 0.0], 1, 10), item([0.0, 0.0, 0.7], 1, 5)];
+        merge_items(&mut items);
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].stack.count(), 15);
+    }
+}
@@ -0,0 +1,37 @@
+//! Voxel engine. `World` holds the chunks, block entities and dropped items and doesn't need a window,
+//! `ChunkRenderer` builds and draws the chunk meshes of a world
+
+#[macro_use]
+extern crate glium;
+
+pub mod actions;
+pub mod block_entity;
+pub mod cache;
+pub mod camera;
+pub mod chunk;
+pub mod chunk_mesh;
+pub mod command;
+pub mod crafting;
+pub mod daylight;
+pub mod input;
+pub mod inventory;
+pub mod item_entity;
+pub mod loader;
+pub mod player;
+pub mod pos;
+pub mod raycast;
+pub mod registry;
+pub mod renderer;
+pub mod replay;
+pub mod selection;
+pub mod settings;
+pub mod shaders;
+pub mod terrain;
+pub mod texture;
+pub mod timestep;
+pub mod ui;
+pub mod worker;
+pub mod world;
+
+mod file_util;
+mod scheduler;
@@ -1,482 +1,525 @@
+use crate::cache::{CacheStats, LruCache};
 use crate::chunk::*;
-use crate::chunk_mesh::*;
 use crate::player;
-use crate::texture::*;
-use glium::Surface;
+use crate::pos::{BlockPos, ChunkPos};
+use crate::scheduler::JobQueue;
+use crate::worker::{default_threads, WorkerPool, WorkerStats};
+
+use serde::{Deserialize, Serialize};
 
 use std::collections::{HashMap, HashSet};
-use std::hash::Hash;
-use std::mem::size_of;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, RwLock};
 
-/// Consists of the chunk coordinate, vertices, and indices
-type MeshData = (ChunkCoord, (Vec<Vertex>, Vec<u16>));
-/// Consists of the chunk coordinate, chunk data, and the surrounding chunks
-type ChunkWithNeighbors = (ChunkCoord, Arc<RwLock<Chunk>>, NeighborChunks);
-/// Used to hold the 6 surrounding chunks of a chunk
-type NeighborChunks = [Arc<RwLock<Chunk>>;6];
-
-/// The coordinates of a chunk in chunk space
-#[derive(Hash, Eq, PartialEq, Debug, Clone)]
-pub struct ChunkCoord {
-    pub x: i32,
-    pub y: i32,
-    pub z: i32,
+/// Consists of the epoch the job was queued in and the coordinate of the chunk to load
+type ChunkJob = (u64, ChunkPos);
+/// Used to hold the 6 surrounding chunks of a chunk, none for the ones that aren't loaded
+pub type NeighborChunks = [Option<Arc<RwLock<Chunk>>>; 6];
+
+/// Number of chunks past its distance a chunk is kept loaded (or meshed or simulated), so chunks near the
+/// border don't load and unload repeatedly while the player moves back and forth
+pub const UNLOAD_MARGIN: u16 = 2;
+
+/// Default number of bytes of unloaded chunks kept in memory
+pub const DEFAULT_CHUNK_CACHE_BUDGET: usize = 64 * 1024 * 1024;
+
+/// Shape of the area around the player that chunks are loaded, meshed or simulated in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LoadShape {
+    /// Ellipsoid with the horizontal distance as radius on x and z and the vertical distance on y
+    Sphere,
+    /// Circle with the horizontal distance as radius, extending the vertical distance up and down
+    Cylinder,
+    /// Axis-aligned box
+    Cube,
 }
 
-impl ChunkCoord {
-    pub fn new(x: i32, y: i32, z: i32) -> Self {
-        ChunkCoord { x, y, z }
-    }
-    pub fn dx(&self, dx: i32) -> ChunkCoord {
-        ChunkCoord {
-            x: self.x + dx,
-            y: self.y,
-            z: self.z,
+/// Area (in chunks) around the player's chunk that chunks are loaded, meshed or simulated in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoadDistance {
+    pub shape: LoadShape,
+    pub horizontal: u16,
+    pub vertical: u16,
+}
+
+impl LoadDistance {
+    pub const fn new(shape: LoadShape, horizontal: u16, vertical: u16) -> LoadDistance {
+        LoadDistance {
+            shape,
+            horizontal,
+            vertical,
         }
     }
-    pub fn dy(&self, dy: i32) -> ChunkCoord {
-        ChunkCoord {
-            x: self.x,
-            y: self.y + dy,
-            z: self.z,
+
+    /// Returns the area grown by `margin` chunks in every direction
+    pub fn loosened(self, margin: u16) -> LoadDistance {
+        LoadDistance {
+            horizontal: self.horizontal.saturating_add(margin),
+            vertical: self.vertical.saturating_add(margin),
+            ..self
         }
     }
-    pub fn dz(&self, dz: i32) -> ChunkCoord {
-        ChunkCoord {
-            x: self.x,
-            y: self.y,
-            z: self.z + dz,
+
+    /// Returns true if `coord` is inside of the area around `center`
+    pub fn contains(&self, center: ChunkPos, coord: ChunkPos) -> bool {
+        let (dx, dy, dz) = (
+            center.x.abs_diff(coord.x) as u128,
+            center.y.abs_diff(coord.y) as u128,
+            center.z.abs_diff(coord.z) as u128,
+        );
+        let (h, v) = (self.horizontal as u128, self.vertical as u128);
+        if dx > h || dz > h || dy > v {
+            return false;
+        }
+        match self.shape {
+            LoadShape::Cube => true,
+            LoadShape::Cylinder => dx * dx + dz * dz <= h * h,
+            // (dx² + dz²) / h² + dy² / v² <= 1, multiplied out so a distance of 0 works
+            LoadShape::Sphere if v == 0 => dx * dx + dz * dz <= h * h,
+            LoadShape::Sphere => (dx * dx + dz * dz) * v * v + dy * dy * h * h <= h * h * v * v,
         }
     }
+
+    /// Iterates over every chunk inside of the area around `center`
+    pub fn chunks_around(self, center: ChunkPos) -> impl Iterator<Item = ChunkPos> {
+        let (h, v) = (self.horizontal as i32, self.vertical as i32);
+        (-h..=h)
+            .flat_map(move |x| (-v..=v).flat_map(move |y| (-h..=h).map(move |z| center.offset(x, y, z))))
+            .filter(move |coord| self.contains(center, *coord))
+    }
+
+    /// Returns the number of chunks in the box around the area, which is at least the number of chunks in it
+    pub fn max_chunks(&self) -> usize {
+        (2 * self.horizontal as usize + 1).pow(2) * (2 * self.vertical as usize + 1)
+    }
 }
 
-/// Responsible for handling of loading and unloading chunks as well as mesh generation/unloading
+/// Responsible for loading, generating, unloading and saving chunks around the player.
+/// Doesn't need a window, so it can be used in tests and on servers
 pub struct ChunkLoader {
-    chunk_map: HashMap<ChunkCoord, Arc<RwLock<Chunk>>>,
-    mesh_map: HashMap<ChunkCoord, ChunkMesh>,
-    queued_chunks: HashSet<ChunkCoord>,
-    queued_meshes: HashSet<ChunkCoord>,
-    load_distance: u16,
-    render_distance: u16,
-    simulation_distance: u16,
-    chunk_rx: std::sync::mpsc::Receiver<(ChunkCoord, Chunk)>,
-    chunk_q: multiqueue::MPMCSender<ChunkCoord>,
-    mesh_rx: std::sync::mpsc::Receiver<MeshData>,
-    mesh_q: multiqueue::MPMCSender<ChunkWithNeighbors>,
-
-    needs_build: Vec<MeshData>,
-    to_generate: Vec<ChunkCoord>,
-
-    texture_map: TextureMap,
+    /// Threads loading and generating chunks. Declared first so they are stopped before the channel is closed
+    workers: WorkerPool<ChunkJob>,
+    chunk_map: HashMap<ChunkPos, Arc<RwLock<Chunk>>>,
+    /// Chunks that left the load distance, kept so they don't have to be loaded again if the player comes back
+    chunk_cache: LruCache<ChunkPos, Arc<RwLock<Chunk>>>,
+    queued_chunks: HashSet<ChunkPos>,
+    load_distance: LoadDistance,
+    /// Loaded chunks, none for the jobs the workers skipped
+    chunk_rx: std::sync::mpsc::Receiver<(ChunkPos, Option<Chunk>)>,
+    chunk_q: JobQueue<ChunkJob>,
+    /// Area queued jobs are still needed for, shared with the worker threads
+    load_area: Arc<RwLock<LoadArea>>,
+    cancelled: Arc<AtomicUsize>,
+    /// Directory modified chunks are saved to
+    save_dir: String,
+    /// If true, updates wait for the queued chunks to be loaded
+    blocking: bool,
 }
 
 impl ChunkLoader {
-    /// Creates a new chunk loader with world seed
-    pub fn new(seed: u32, texture_map: TextureMap) -> Self {
-        // Distance from camera that chunks are rendered (number of chunks)
-        let render_distance = 18;
+    /// Creates a new chunk loader with world seed that saves chunks to `save_dir`
+    pub fn new(seed: u32, save_dir: &str) -> Self {
         // Distance from camera that chunks are generated/loaded
-        let load_distance = render_distance + 1;
-        // Distance from camera that AI and physics are updated
-        let simulation_distance = 4;
+        let load_distance = LoadDistance::new(LoadShape::Cylinder, 19, 9);
 
         let generator = crate::terrain::TerrainGenerator::new(seed);
 
-        // Multithreaded queue for sending coordinates of chunks that need to be loaded to worker threads
-        let (chunk_q, chunk_q_rec): (
-            multiqueue::MPMCSender<ChunkCoord>,
-            multiqueue::MPMCReceiver<ChunkCoord>,
-        ) = multiqueue::mpmc_queue(
-            (load_distance * load_distance * load_distance) as u64 * size_of::<ChunkCoord>() as u64,
-        );
+        // Queue of coordinates of chunks that need to be loaded by worker threads, nearest first
+        let chunk_q: JobQueue<ChunkJob> = JobQueue::new(load_distance.max_chunks());
 
-        // Multithreaded queue for sending chunk data that need to be loaded to worker threads for building meshes
-        let (mesh_q, mesh_q_rec): (
-            multiqueue::MPMCSender<ChunkWithNeighbors>,
-            multiqueue::MPMCReceiver<ChunkWithNeighbors>,
-        ) = multiqueue::mpmc_queue(
-            (render_distance * render_distance * render_distance) as u64
-            * (size_of::<ChunkCoord>()
-            + size_of::<Arc<RwLock<Chunk>>>()
-            + size_of::<NeighborChunks>()) as u64,
-        );
+        let load_area = Arc::new(RwLock::new(LoadArea::new(load_distance)));
+        let cancelled = Arc::new(AtomicUsize::new(0));
 
         // Channel for sending loaded chunk back to main thread
         let (chunk_tx, chunk_rx) = std::sync::mpsc::channel();
 
-        // Channel for sending meshes back to main thread
-        let (mesh_tx, mesh_rx) = std::sync::mpsc::channel();
-
         // Threads for loading chunks
-        for _ in 0..4 {
+        let workers = {
             let tx = chunk_tx.clone();
-            let chunk_q_rec = chunk_q_rec.clone();
-            let generator = generator.clone();
-
-            std::thread::spawn(move || loop {
-                // Receive coordinate of chunk to be loaded
-                let chunk_coord_res = chunk_q_rec.recv();
-
-                match chunk_coord_res {
-                    Ok(chunk_coord) => {
-                        // Generate chunk
-                        // let chunk = match try_load_from_file(&chunk_coord) {
-                        //     None => generator.generate_chunk((chunk_coord.x, chunk_coord.y, chunk_coord.z)),
-                        //     Some(chunk) => chunk,
-                        // };
-                        let chunk =
-                            generator.generate_chunk((chunk_coord.x, chunk_coord.y, chunk_coord.z));
-
-                        // Send generated chunk back to main thread
-                        match tx.send((chunk_coord, chunk)) {
-                            Ok(_) => (),
-                            Err(e) => {
-                                println!("Error sending chunk to main thread: {}", e);
-                            }
-                        }
-                    }
-                    Err(_) => {
+            let load_area = load_area.clone();
+            let cancelled = cancelled.clone();
+            let save_dir = save_dir.to_string();
+
+            let work = move |&(epoch, chunk_coord): &ChunkJob| {
+                // Skip chunks the player moved away from after they were queued
+                if !load_area.read().unwrap().needs(epoch, &chunk_coord) {
+                    cancelled.fetch_add(1, Ordering::Relaxed);
+                    match tx.send((chunk_coord, None)) {
+                        Ok(_) => (),
+                        Err(e) => println!("Error sending chunk to main thread: {}", e),
                     }
+                    return;
                 }
-            });
-        }
 
-        // Threads for loading meshes
-        for _ in 0..4 {
-            let tx = mesh_tx.clone();
-            let mesh_q_rec = mesh_q_rec.clone();
-            let texture_info = texture_map.info.clone();
-
-            std::thread::spawn(move || loop {
-                // Receive data for generating mesh
-                if let Ok((coord, chunk, neighbors)) = mesh_q_rec.recv() {
-                    // Generate mesh data
-                    let mesh_data = chunk.read().unwrap().gen_mesh(
-                        neighbors,
-                        &texture_info,
-                    );
-
-                    // Send mesh data to main thread
-                    match tx.send((coord, mesh_data)) {
-                        Ok(_) => (),
-                        Err(e) => {
-                            println!("Error sending mesh data to main thread: {}", e);
-                        }
-                    }
+                // Load chunk if it was saved, otherwise generate it
+                let chunk = match Chunk::load(chunk_coord, &chunk_path(&save_dir, &chunk_coord)) {
+                    None => generator.generate_chunk(chunk_coord),
+                    Some(chunk) => chunk,
                 };
-            });
-        }
+
+                // Send generated chunk back to main thread
+                match tx.send((chunk_coord, Some(chunk))) {
+                    Ok(_) => (),
+                    Err(e) => {
+                        println!("Error sending chunk to main thread: {}", e);
+                    }
+                }
+            };
+            // Chunks that failed to load are queued again by a later update
+            let on_panic = move |(_, chunk_coord): ChunkJob| match chunk_tx.send((chunk_coord, None)) {
+                Ok(_) => (),
+                Err(e) => println!("Error sending chunk to main thread: {}", e),
+            };
+            WorkerPool::new("Chunk", default_threads(), chunk_q.clone(), work, on_panic)
+        };
 
         ChunkLoader {
+            workers,
             chunk_map: HashMap::new(),
-            mesh_map: HashMap::new(),
+            chunk_cache: LruCache::new(DEFAULT_CHUNK_CACHE_BUDGET),
             queued_chunks: HashSet::new(),
-            queued_meshes: HashSet::new(),
             load_distance,
-            render_distance,
-            simulation_distance,
             chunk_rx,
             chunk_q,
-            mesh_rx,
-            mesh_q,
-            needs_build: Vec::with_capacity(
-                (12 * load_distance * load_distance * load_distance) as usize,
-            ),
-            to_generate: Vec::with_capacity(
-                (8 * load_distance * load_distance * load_distance) as usize,
-            ),
-            texture_map,
+            load_area,
+            cancelled,
+            save_dir: save_dir.to_string(),
+            blocking: false,
         }
     }
 
     /// Queues the chunks that need to be loaded based on player position, then
-    /// inserts the next loaded chunk and generates meshes for chunks within render distance.
+    /// inserts the loaded chunks and unloads the chunks out of load distance.
     /// To be called on the main thread once per game tick.
-    pub fn update(&mut self, player: &crate::player::Player, display: &glium::Display) {
+    pub fn update(&mut self, player: &player::Player) {
+        let player_chunk = player_chunk(player);
+
+        // When the player enters another chunk, start a new epoch, drop the queued jobs that are no longer
+        // needed and order the remaining ones for the new position
+        if self.load_area.write().unwrap().set_center(&player_chunk) {
+            self.drop_unneeded_jobs();
+            self.chunk_q
+                .reprioritize(|(_, coord)| chunk_priority(&player_chunk, coord));
+        }
+        let epoch = self.load_area.read().unwrap().epoch;
+
         // Check chunks surrounding player and queue them to be loaded if not already
-        for x in (player.x as i32 / CHUNK_SIZE.0 as i32 - self.load_distance as i32)
-            ..(player.x as i32 / CHUNK_SIZE.0 as i32 + self.load_distance as i32)
-        {
-            for y in (player.y as i32 / CHUNK_SIZE.1 as i32 - self.load_distance as i32)
-                ..(player.y as i32 / CHUNK_SIZE.1 as i32 + self.load_distance as i32)
-            {
-                for z in (player.z as i32 / CHUNK_SIZE.2 as i32 - self.load_distance as i32)
-                    ..(player.z as i32 / CHUNK_SIZE.2 as i32 + self.load_distance as i32)
-                {
-                    let chunk_coord = ChunkCoord { x, y, z };
-                    let mut to_update = false;
-
-                    match &self.chunk_map.get(&chunk_coord) {
-                        None => match self.queued_chunks.get(&chunk_coord) {
-                            // Queue chunk to be loaded
-                            None => match self.chunk_q.try_send(chunk_coord.clone()) {
-                                Ok(_) => {
-                                    to_update = true;
-                                }
-                                Err(e) => {
-                                    println!("Error sending chunk coord to workers: Queue Full");
-                                }
-                            },
-                            Some(_) => (),
-                        },
-                        Some(_chunk) => {}
-                    }
-                    if to_update {
-                        self.queued_chunks.insert(chunk_coord);
-                    }
-                }
+        for chunk_coord in self.load_distance.chunks_around(player_chunk) {
+            if self.chunk_map.contains_key(&chunk_coord) || self.queued_chunks.contains(&chunk_coord) {
+                continue;
+            }
+            if let Some(chunk) = self.chunk_cache.take(&chunk_coord) {
+                self.chunk_map.insert(chunk_coord, chunk);
+                continue;
+            }
+
+            // Queue chunk to be loaded. If the queue is full, the least urgent chunk is left
+            // out and queued again by a later update
+            let priority = chunk_priority(&player_chunk, &chunk_coord);
+            self.queued_chunks.insert(chunk_coord);
+            if let Some((_, rejected)) = self.chunk_q.push((epoch, chunk_coord), priority) {
+                self.queued_chunks.remove(&rejected);
             }
         }
 
         // Receive loaded chunk from worker
         while let Ok((coord, chunk)) = self.chunk_rx.try_recv() {
-            let chunk = chunk;
-            self.chunk_map
-                .insert(coord.clone(), Arc::new(RwLock::new(chunk)));
-            self.queued_chunks.remove(&coord);
+            self.receive(coord, chunk);
         }
-
-        // Check loaded chunks if they are in render distance and if their meshes are loaded.
-        // If not, add them to list of meshes to be generated
-        for (coord, chunk) in &mut self.chunk_map {
-            if !chunk.read().unwrap().is_empty()
-                && in_distance(player, coord, self.render_distance)
-            {
-                match self.mesh_map.get(coord) {
-                    None => {
-                        chunk.write().unwrap().request_update();
-                        self.to_generate.push(coord.clone());
-                    }
-                    Some(_) => {
-                        if chunk.read().unwrap().needs_update() {
-                            self.to_generate.push(coord.clone());
-                        }
-                    }
+        while self.blocking && !self.queued_chunks.is_empty() {
+            match self.chunk_rx.recv() {
+                Ok((coord, chunk)) => self.receive(coord, chunk),
+                Err(e) => {
+                    println!("Error receiving chunk: {}", e);
+                    break;
                 }
             }
         }
 
-        // Find neighbor chunks and send chunk data and neighbors' chunk data to worker thread for mesh building
-        for coord in &self.to_generate {
-            match self.queued_meshes.get(coord) {
-                None => {
-                    let neighbors = match get_neighbors(&self.chunk_map, coord) {
-                        None => continue,
-                        Some(neighbors) => neighbors,
-                    };
-
-                    match self.mesh_q.try_send((
-                        coord.clone(),
-                        self.chunk_map.get(coord).unwrap().clone(),
-                        neighbors,
-                    )) {
-                        Ok(_) => {
-                            self.queued_meshes.insert(coord.clone());
-                        }
-                        Err(e) => {
-                            println!("Error sending chunk data for mesh generation to workers: Queue full");
-                        }
-                    }
+        // Unload chunks out of load distance into the cache, saving the ones that were modified
+        let keep_distance = self.load_distance.loosened(UNLOAD_MARGIN);
+        let unloaded: Vec<ChunkPos> = self
+            .chunk_map
+            .keys()
+            .filter(|coord| !in_distance(player, coord, keep_distance))
+            .copied()
+            .collect();
+        for coord in unloaded {
+            let chunk = self.chunk_map.remove(&coord).unwrap();
+            let size = {
+                let mut chunk = chunk.write().unwrap();
+                if chunk.is_modified() {
+                    chunk.save(&chunk_path(&self.save_dir, &coord));
                 }
-                Some(_) => {}
-            }
+                chunk.memory_size()
+            };
+            self.chunk_cache.insert(coord, chunk, size);
         }
+    }
 
-        // Receive mesh data from worker threads
-        while let Ok((coord, mesh_data)) = self.mesh_rx.try_recv() {
-            self.queued_meshes.remove(&coord);
-            self.needs_build.push((coord.clone(), mesh_data));
+    /// Drops the queued jobs of chunks out of the load area, after it was moved or resized
+    fn drop_unneeded_jobs(&mut self) {
+        let area = self.load_area.read().unwrap();
+        for (_, coord) in self.chunk_q.retain(|(epoch, coord)| area.needs(*epoch, coord)) {
+            self.queued_chunks.remove(&coord);
+            self.cancelled.fetch_add(1, Ordering::Relaxed);
         }
+    }
 
-        // Build meshes from mesh data and insert them into mesh map
-        for (coord, vertices) in &self.needs_build {
-            match glium::vertex::VertexBuffer::new(display, &vertices.0[..]) {
-                Ok(vb) => {
-                    let mesh = ChunkMesh::new(vb, {
-                        match glium::IndexBuffer::new(
-                            display,
-                            glium::index::PrimitiveType::TrianglesList,
-                            &vertices.1[..],
-                        ) {
-                            Ok(buf) => buf,
-                            Err(err) => {
-                                panic!("Error making index buffer: {}", err);
-                            }
-                        }
-                    });
-
-                    self.mesh_map.insert(coord.clone(), mesh);
-                    self.chunk_map
-                        .get_mut(coord)
-                        .unwrap()
-                        .write()
-                        .unwrap()
-                        .set_updated();
-                }
-                Err(e) => {
-                    println!("Error creating vertex buffer: {:?}", e);
-                }
-            }
+    fn receive(&mut self, coord: ChunkPos, chunk: Option<Chunk>) {
+        if let Some(chunk) = chunk {
+            self.chunk_map.insert(coord, Arc::new(RwLock::new(chunk)));
         }
-
-        // Unload meshes out of render distance
-        // TODO: Don't drop meshes out of render distance, just don't render them so they don't have to be rebuilt
-        // (Be careful of making sure that they are updated if they come back into render distance)
-        self.mesh_map
-            .retain(|coord, _| in_distance(player, coord, self.render_distance));
-
-        // Unload chunks out of load distance
-        self.chunk_map
-            .retain(|coord, _| in_distance(player, coord, self.load_distance));
-
-        // Clear temporary lists
-        self.needs_build.clear();
-        self.to_generate.clear();
+        self.queued_chunks.remove(&coord);
     }
 
     /// Returns block data based on coordinate (world space). Returns none if block is in unloaded chunk
-    pub fn get_block(&self, [x, y, z]: [i32; 3]) -> Option<Block> {
-        let chunk_coord = ChunkCoord {
-            x: (x as f32 / CHUNK_SIZE.0 as f32).floor() as i32,
-            y: (y as f32 / CHUNK_SIZE.1 as f32).floor() as i32,
-            z: (z as f32 / CHUNK_SIZE.2 as f32).floor() as i32,
-        };
+    pub fn get_block(&self, pos: BlockPos) -> Option<Block> {
+        let (chunk_coord, local) = pos.split();
         match self.chunk_map.get(&chunk_coord) {
             None => None,
-            Some(chunk) => chunk.read().unwrap().get_block((
-                (x - chunk_coord.x * CHUNK_SIZE.0 as i32) as usize,
-                (y - chunk_coord.y * CHUNK_SIZE.1 as i32) as usize,
-                (z - chunk_coord.z * CHUNK_SIZE.2 as i32) as usize,
-            )),
+            Some(chunk) => chunk.read().unwrap().get_block(local),
         }
     }
 
-    /// Sets block
-    pub fn set_block(&self, [x, y, z]: [i32; 3], block: Block) {
-        let chunk_coord = ChunkCoord {
-            x: (x as f32 / CHUNK_SIZE.0 as f32).floor() as i32,
-            y: (y as f32 / CHUNK_SIZE.1 as f32).floor() as i32,
-            z: (z as f32 / CHUNK_SIZE.2 as f32).floor() as i32,
-        };
-        match self.chunk_map.get(&chunk_coord) {
-            None => (),
-            Some(chunk) => {
-                if chunk.write().unwrap().set_block((
-                    (x - chunk_coord.x * CHUNK_SIZE.0 as i32) as usize,
-                    (y - chunk_coord.y * CHUNK_SIZE.1 as i32) as usize,
-                    (z - chunk_coord.z * CHUNK_SIZE.2 as i32) as usize,
-                ), block) {
-                    if let Some(neighbors) = get_neighbors(&self.chunk_map, &chunk_coord) {
-                        neighbors.into_iter().for_each(|n| n.write().unwrap().request_update());
-                    }
-                }
-            },
-        }
+    /// Returns the chunk containing a block (world space). Returns none if chunk is not loaded
+    pub fn get_chunk(&self, pos: BlockPos) -> Option<Arc<RwLock<Chunk>>> {
+        self.chunk_map.get(&pos.chunk()).cloned()
     }
 
-    /// Returns chunk data based on coordinate (chunk space). Returns none if chunk is not loaded
-    pub fn get_chunk(&self, (i, j, k): (i32, i32, i32)) -> Option<Arc<RwLock<Chunk>>> {
-        let chunk_coord = ChunkCoord {
-            x: (i as f32 / CHUNK_SIZE.0 as f32).floor() as i32,
-            y: (j as f32 / CHUNK_SIZE.1 as f32).floor() as i32,
-            z: (k as f32 / CHUNK_SIZE.2 as f32).floor() as i32,
-        };
+    /// Returns chunk data based on chunk coordinate. Returns none if chunk is not loaded
+    pub fn chunk(&self, coord: &ChunkPos) -> Option<&Arc<RwLock<Chunk>>> {
+        self.chunk_map.get(coord)
+    }
 
-        self.chunk_map.get(&chunk_coord).cloned()
-    }
-
-    /// Renders chunk meshes
-    pub fn render(
-        &self,
-        target: &mut glium::Frame,
-        program: &glium::Program,
-        view_projection: [[f32; 4]; 4],
-        u_light: [f32; 3],
-        params: &glium::DrawParameters,
-    ) {
-        let frustum = crate::camera::Frustum::new(&view_projection);
-        for (chunk_coord, chunk_mesh) in &self.mesh_map {
-            if frustum.contains(&[chunk_coord.x, chunk_coord.y, chunk_coord.z]) {
-                match target.draw(
-                    chunk_mesh.get_mesh(),
-                    chunk_mesh.get_indices(),
-                    program,
-                    &uniform! {
-                        view_projection: view_projection,
-                        u_light: u_light,
-                        diffuse_tex: self.texture_map.base.sampled().magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest).minify_filter(glium::uniforms::MinifySamplerFilter::LinearMipmapLinear).anisotropy(32),
-                        normal_tex: &self.texture_map.normal,
-                        chunk_coords: [(chunk_coord.x as i32 * CHUNK_SIZE.0 as i32) as f32, (chunk_coord.y as i32 * CHUNK_SIZE.1 as i32) as f32, (chunk_coord.z as i32 * CHUNK_SIZE.2 as i32) as f32]
-                    },
-                    params,
-                ) {
-                    Ok(_) => (),
-                    Err(e) => {
-                        println!("Error while drawing: {}", e);
-                    }
-                }
+    /// Returns every loaded chunk
+    pub fn chunks(&self) -> &HashMap<ChunkPos, Arc<RwLock<Chunk>>> {
+        &self.chunk_map
+    }
+
+    /// Returns the 6 surrounding chunks of a chunk, none for the ones that aren't loaded
+    pub fn neighbors(&self, coord: &ChunkPos) -> NeighborChunks {
+        get_neighbors(&self.chunk_map, coord)
+    }
+
+    /// Sets the number of threads loading and generating chunks
+    pub fn set_worker_threads(&mut self, threads: usize) {
+        self.workers.set_threads(threads);
+    }
+
+    /// Returns the number of jobs, panics and utilisation of every thread loading and generating chunks
+    pub fn worker_stats(&self) -> Vec<WorkerStats> {
+        self.workers.stats()
+    }
+
+    /// Saves every loaded chunk that was modified
+    pub fn save_all(&self) {
+        for (coord, chunk) in &self.chunk_map {
+            let mut chunk = chunk.write().unwrap();
+            if chunk.is_modified() {
+                chunk.save(&chunk_path(&self.save_dir, coord));
             }
         }
     }
 
+    /// Removes the queued load jobs of every chunk between `min` and `max` (inclusive, chunk space).
+    /// Chunks that are still needed are queued again by the next update. Returns the number of removed jobs
+    pub fn cancel_region(&mut self, min: &ChunkPos, max: &ChunkPos) -> usize {
+        let removed = self.chunk_q.retain(|(_, coord)| !in_region(coord, min, max));
+        self.cancelled.fetch_add(removed.len(), Ordering::Relaxed);
+        let count = removed.len();
+        for (_, coord) in removed {
+            self.queued_chunks.remove(&coord);
+        }
+        count
+    }
+
+    /// Returns the area around the player that chunks are loaded in
+    pub fn load_distance(&self) -> LoadDistance {
+        self.load_distance
+    }
+
+    /// Sets the area around the player that chunks are loaded in. Takes effect on the next update
+    pub fn set_load_distance(&mut self, distance: LoadDistance) {
+        self.load_distance = distance;
+        self.load_area.write().unwrap().set_distance(distance);
+        self.drop_unneeded_jobs();
+    }
+
+    /// Makes updates wait until every queued chunk is loaded, so the same player movement always
+    /// loads the same chunks at the same ticks. Used to record and replay input
+    pub fn set_blocking(&mut self, blocking: bool) {
+        self.blocking = blocking;
+    }
+
+    /// Sets the number of bytes of unloaded chunks kept in memory
+    pub fn set_chunk_cache_budget(&mut self, bytes: usize) {
+        self.chunk_cache.set_budget(bytes);
+    }
+
+    /// Returns the hits, misses and size of the cache of unloaded chunks
+    pub fn chunk_cache_stats(&self) -> CacheStats {
+        self.chunk_cache.stats()
+    }
+
+    /// Returns the number of chunk load jobs that were cancelled before they were finished
+    pub fn get_number_of_cancelled_chunks(&self) -> usize {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
     /// Returns the number of loaded chunks
     pub fn get_number_of_loaded_chunks(&self) -> usize {
         self.chunk_map.len()
     }
+}
+
+/// Area around the player's chunk that jobs are needed for. The epoch is increased whenever the player
+/// enters another chunk, so jobs queued in the current epoch are known to be needed without checking their distance
+pub struct LoadArea {
+    pub epoch: u64,
+    center: Option<ChunkPos>,
+    distance: LoadDistance,
+}
+
+impl LoadArea {
+    pub fn new(distance: LoadDistance) -> LoadArea {
+        LoadArea {
+            epoch: 0,
+            center: None,
+            distance,
+        }
+    }
 
-    /// Returns the number of loaded meshes
-    pub fn get_number_of_loaded_meshes(&self) -> usize {
-        self.mesh_map.len()
+    /// Moves the area, starting a new epoch. Returns false if the area was already centered on `center`
+    pub fn set_center(&mut self, center: &ChunkPos) -> bool {
+        if self.center.as_ref() == Some(center) {
+            return false;
+        }
+        self.epoch += 1;
+        self.center = Some(*center);
+        true
+    }
+
+    /// Resizes the area, starting a new epoch
+    pub fn set_distance(&mut self, distance: LoadDistance) {
+        self.epoch += 1;
+        self.distance = distance;
+    }
+
+    /// Returns true if a job for `coord` queued in `epoch` is still needed
+    pub fn needs(&self, epoch: u64, coord: &ChunkPos) -> bool {
+        if epoch == self.epoch {
+            return true;
+        }
+        match self.center {
+            None => true,
+            Some(center) => self.distance.contains(center, *coord),
+        }
     }
 }
 
+/// Returns the coordinate of the chunk the player is in
+pub fn player_chunk(player: &player::Player) -> ChunkPos {
+    ChunkPos::containing([player.x, player.y, player.z])
+}
+
+/// Returns the priority of loading a chunk, which is its squared distance (chunk space) from the player's chunk.
+/// The offset wraps around at the edge of the world like chunk positions, instead of overflowing
+pub fn chunk_priority(player_chunk: &ChunkPos, coord: &ChunkPos) -> f32 {
+    let offset = *coord - *player_chunk;
+    let (dx, dy, dz) = (offset.x as f32, offset.y as f32, offset.z as f32);
+    dx * dx + dy * dy + dz * dz
+}
+
+/// Returns true if `coord` is between `min` and `max` (inclusive)
+pub fn in_region(coord: &ChunkPos, min: &ChunkPos, max: &ChunkPos) -> bool {
+    (min.x..=max.x).contains(&coord.x)
+        && (min.y..=max.y).contains(&coord.y)
+        && (min.z..=max.z).contains(&coord.z)
+}
+
+/// Returns true if `coord` is inside of the area `distance` around the player's chunk
 #[inline]
-fn in_distance(player: &player::Player, coord: &ChunkCoord, distance: u16) -> bool {
-    ((player.x as i32 - (coord.x * CHUNK_SIZE.0 as i32)) / CHUNK_SIZE.0 as i32).abs()
-        <= distance as i32
-        && ((player.y as i32 - (coord.y * CHUNK_SIZE.1 as i32)) / CHUNK_SIZE.1 as i32).abs()
-            <= distance as i32
-        && ((player.z as i32 - (coord.z * CHUNK_SIZE.2 as i32)) / CHUNK_SIZE.2 as i32).abs()
-            <= distance as i32
+pub fn in_distance(player: &player::Player, coord: &ChunkPos, distance: LoadDistance) -> bool {
+    distance.contains(player_chunk(player), *coord)
+}
+
+/// Returns the path a chunk is saved to
+fn chunk_path(save_dir: &str, chunk_coord: &ChunkPos) -> String {
+    format!(
+        "{}/x{}y{}z{}.chunk",
+        save_dir, chunk_coord.x, chunk_coord.y, chunk_coord.z
+    )
+}
+fn get_neighbors(chunk_map: &HashMap<ChunkPos, Arc<RwLock<Chunk>>>, coord: &ChunkPos) -> NeighborChunks {
+    coord.neighbors().map(|n| chunk_map.get(&n).cloned())
 }
 
-// fn try_load_from_file(chunk_coord: &ChunkCoord) -> Option<Chunk> {
-//     let path = format!(
-//         "chunks/x{}y{}z{}.chunk",
-//         chunk_coord.x, chunk_coord.y, chunk_coord.z
-//     );
-//     match read_chunk_data_from_file(&path) {
-//         None => None,
-//         Some(data) => Some(Chunk::from_data(chunk_coord.clone(), data)),
-//     }
-// }
-
-fn get_neighbors(chunk_map: &HashMap<ChunkCoord, Arc<RwLock<Chunk>>>, coord: &ChunkCoord) -> Option<[Arc<RwLock<Chunk>>;6]> {
-    Some([
-        match chunk_map.get(&coord.dx(1)) {
-            None => return None,
-            Some(chunk) => chunk.clone(),
-        },
-        match chunk_map.get(&coord.dx(-1)) {
-            None => return None,
-            Some(chunk) => chunk.clone(),
-        },
-        match chunk_map.get(&coord.dy(-1)) {
-            None => return None,
-            Some(chunk) => chunk.clone(),
-        },
-        match chunk_map.get(&coord.dy(1)) {
-            None => return None,
-            Some(chunk) => chunk.clone(),
-        },
-        match chunk_map.get(&coord.dz(1)) {
-            None => return None,
-            Some(chunk) => chunk.clone(),
-        },
-        match chunk_map.get(&coord.dz(-1)) {
-            None => return None,
-            Some(chunk) => chunk.clone(),
-        },
-    ])
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CENTER: ChunkPos = ChunkPos::new(-3, 7, i32::MAX - 20);
+
+    fn count(distance: LoadDistance) -> usize {
+        distance.chunks_around(CENTER).count()
+    }
+
+    #[test]
+    fn shapes_nest_inside_each_other() {
+        let sphere = count(LoadDistance::new(LoadShape::Sphere, 10, 10));
+        let cylinder = count(LoadDistance::new(LoadShape::Cylinder, 10, 10));
+        let cube = count(LoadDistance::new(LoadShape::Cube, 10, 10));
+        assert_eq!(cube, 21 * 21 * 21);
+        assert!(sphere < cylinder && cylinder < cube);
+        // On a grid this small, a sphere fills about 45% and a cylinder about 72% of its box
+        assert!((0.4..0.5).contains(&(sphere as f32 / cube as f32)));
+        assert!((0.65..0.8).contains(&(cylinder as f32 / cube as f32)));
+    }
+
+    #[test]
+    fn vertical_distance_is_separate() {
+        for shape in [LoadShape::Sphere, LoadShape::Cylinder, LoadShape::Cube] {
+            let distance = LoadDistance::new(shape, 8, 2);
+            assert!(distance.contains(CENTER, CENTER.offset(8, 0, 0)));
+            assert!(distance.contains(CENTER, CENTER.offset(0, -2, 0)));
+            assert!(!distance.contains(CENTER, CENTER.offset(0, 3, 0)));
+            assert!(!distance.contains(CENTER, CENTER.offset(0, 0, -9)));
+            assert!(distance.chunks_around(CENTER).all(|c| (c.y - CENTER.y).abs() <= 2));
+            assert_eq!(distance.chunks_around(CENTER).count(), count(distance));
+        }
+        let flat = LoadDistance::new(LoadShape::Sphere, 3, 0);
+        assert!(flat.chunks_around(CENTER).all(|c| c.y == CENTER.y));
+        assert_eq!(count(flat), count(LoadDistance::new(LoadShape::Cylinder, 3, 0)));
+        assert_eq!(count(LoadDistance::new(LoadShape::Sphere, 0, 0)), 1);
+    }
+
+    #[test]
+    fn loosened_distance_keeps_chunks_near_border() {
+        let distance = LoadDistance::new(LoadShape::Cylinder, 5, 3);
+        let keep = distance.loosened(UNLOAD_MARGIN);
+        assert!(distance.chunks_around(CENTER).all(|c| keep.contains(CENTER, c)));
+        // One chunk of movement doesn't unload anything at the border
+        let moved = CENTER.offset(1, 0, 0);
+        assert!(distance.chunks_around(CENTER).all(|c| keep.contains(moved, c)));
+        assert!(!distance.contains(moved, CENTER.offset(-5, 0, 0)));
+        assert_eq!(LoadDistance::new(LoadShape::Cube, u16::MAX, 1).loosened(1).horizontal, u16::MAX);
+    }
+
+    #[test]
+    fn extreme_distances_do_not_overflow() {
+        let distance = LoadDistance::new(LoadShape::Sphere, u16::MAX, u16::MAX);
+        assert!(distance.contains(CENTER, CENTER.offset(u16::MAX as i32, 0, 0)));
+        assert!(!distance.contains(CENTER, CENTER.offset(u16::MAX as i32, 1, 1)));
+        assert!(!distance.contains(ChunkPos::new(i32::MIN, 0, 0), ChunkPos::new(i32::MAX, 0, 0)));
+    }
+
+    #[test]
+    fn priorities_at_the_edge_of_the_world() {
+        let edge = ChunkPos::new(i32::MAX, i32::MIN, i32::MAX);
+        assert_eq!(chunk_priority(&edge, &edge), 0.0);
+        assert_eq!(chunk_priority(&edge, &edge.offset(-2, 1, 0)), 5.0);
+        assert_eq!(chunk_priority(&edge, &edge.offset(1, -1, 1)), 3.0);
+        assert_eq!(chunk_priority(&CENTER, &CENTER.offset(0, 0, 21)), 21.0 * 21.0);
+    }
+}
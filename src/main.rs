@@ -1,23 +1,15 @@
-#[macro_use]
-extern crate glium;
-
-mod camera;
-mod chunk;
-mod chunk_mesh;
 mod clipboard;
-mod file_util;
-mod input;
-mod loader;
-mod player;
-mod shaders;
-mod texture;
-mod terrain;
-mod inventory;
-
-use player::Player;
-use shaders::load_shader;
-
-use std::ops::Mul;
+
+use vixen::actions::{Action, Actions};
+use vixen::daylight::{self, Lighting};
+use vixen::player::Player;
+use vixen::replay::Recording;
+use vixen::shaders::load_shader;
+use vixen::settings::Settings;
+use vixen::timestep::{Timestep, TICK_DELTA, TICK_RATE};
+use vixen::{input, inventory, player, registry, renderer, selection, texture, ui, world};
+
+use std::sync::Arc;
 use std::time::Instant;
 
 use imgui::*;
@@ -25,10 +17,17 @@ use imgui::{Context, FontConfig, FontSource, Ui};
 use imgui_glium_renderer::Renderer;
 use imgui_winit_support::{HiDpiMode, WinitPlatform};
 
-use glium::glutin::event::{ElementState::*, Event, VirtualKeyCode, WindowEvent};
+use glium::glutin::event::{ElementState, Event, WindowEvent};
 use glium::glutin::event_loop::{ControlFlow, EventLoop};
 use glium::{glutin, Surface};
 
+const INVENTORY_PATH: &str = "saves/inventory.dat";
+/// Directory modified chunks are saved to
+const CHUNK_SAVE_DIR: &str = "saves/chunks";
+const SETTINGS_PATH: &str = "settings.json";
+/// Directory chunks are saved to while recording or replaying input, emptied when they start
+const RECORDING_SAVE_DIR: &str = "saves/recording";
+
 struct System {
     event_loop: EventLoop<()>,
     display: glium::Display,
@@ -38,43 +37,98 @@ struct System {
 }
 
 fn main() {
-    let mut sys = init();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let record_path = match args.as_slice() {
+        [flag, path] if flag == "--replay" => {
+            replay(path);
+            return;
+        }
+        [flag, path] if flag == "--record" => Some(path.clone()),
+        [] => None,
+        _ => {
+            println!("Usage: vixen [--record <file> | --replay <file>]");
+            return;
+        }
+    };
+
+    let mut settings = Settings::load(SETTINGS_PATH);
+    let mut sys = init(&settings);
 
     let diffuse = load_shader("diffuse", &sys.display);
+    let item_shader = load_shader("item", &sys.display);
+    let outline_shader = load_shader("outline", &sys.display);
+    let crack_shader = load_shader("crack", &sys.display);
 
     let texture_map = texture::load_texture_map("res/map2.png", None, &sys.display);
 
-    let mut chunk_loader = loader::ChunkLoader::new(0, texture_map);
+    let registry = Arc::new(registry::Registry::load("res"));
+
+    // Recordings start in a world without saved chunks, so they can be replayed in a new one
+    let save_dir = match record_path {
+        Some(_) => {
+            clear_dir(RECORDING_SAVE_DIR);
+            RECORDING_SAVE_DIR
+        }
+        None => CHUNK_SAVE_DIR,
+    };
+    let mut world = world::World::new(settings.seed, registry.clone(), save_dir);
+    world.set_blocking_loads(record_path.is_some());
+    let mut chunk_renderer = renderer::ChunkRenderer::new(texture_map.info.clone());
     let mut input = input::Input::new();
+    let mut actions = Actions::new(settings.bindings.clone());
     let mut player = player::Player::default();
-
-    
-
-    match sys.display.gl_window().window().set_cursor_grab(true) {
-        Ok(_) => (),
-        Err(e) => println!("Error: {}", e),
+    if let Some(inventory) = inventory::Inventory::load(INVENTORY_PATH) {
+        player.inventory = inventory;
     }
-    sys.display.gl_window().window().set_cursor_visible(false);
+    let mut inventory_ui = ui::InventoryUi::default();
+    let mut console = ui::Console::default();
+    apply_settings(&settings, &mut world, &mut chunk_renderer, &mut player, &mut actions, &sys.display);
+    let mut recording = record_path.map(|path| {
+        let recording = Recording::new(settings.seed, settings.load_distance(), settings.bindings.clone(), &player);
+        (path, recording)
+    });
+
+    let mut cursor_free = false;
+    let mut cursor_captured = true;
+    capture_cursor(&sys.display, &mut sys.imgui, true);
 
     // EVENT LOOP
 
     let mut last_frame = Instant::now();
     let mut last_tick = last_frame;
+    let mut timestep = Timestep::new();
+    // Camera at the end of the tick before the last one, rendered interpolated towards the current one
+    let mut previous_camera = player.get_camera().clone();
     let mut frames = 0;
     let mut last_q_sec = last_frame;
     let mut fps: f64 = 0.0;
 
-    sys.event_loop
-        .run(move |event, _, control_flow| match event {
+    sys.event_loop.run(move |event, _, control_flow| {
+        // imgui sees every event first, the game only gets the mouse when it isn't over a window
+        {
+            let gl_window = sys.display.gl_window();
+            sys.platform
+                .handle_event(sys.imgui.io_mut(), gl_window.window(), &event);
+        }
+        let ui_mouse = sys.imgui.io().want_capture_mouse;
+        // Typing into the console or a sign doesn't move the player
+        let ui_keyboard = sys.imgui.io().want_capture_keyboard;
+
+        match event {
             Event::WindowEvent { event, .. } => match event {
                 WindowEvent::CloseRequested => {
                     *control_flow = ControlFlow::Exit;
-                    return;
                 }
-                WindowEvent::MouseInput { button, state, .. } => {
+                // Releases always reach the game, so buttons pressed before the mouse moved over a window don't stick
+                WindowEvent::MouseInput { button, state, .. }
+                    if !ui_mouse || state == ElementState::Released =>
+                {
                     input.update_mouse_button(button, state);
                 }
-                _ => return,
+                WindowEvent::MouseWheel { delta, .. } if !ui_mouse => {
+                    input.update_scroll(delta);
+                }
+                _ => (),
             },
             Event::NewEvents(cause) => {
                 match cause {
@@ -87,20 +141,40 @@ fn main() {
             }
             Event::MainEventsCleared => {
                 let now = std::time::Instant::now();
-                let delta = (now - last_tick).as_secs_f32();
+                let ticks = timestep.advance(now - last_tick);
                 last_tick = now;
 
-                if input.is_key_released(&VirtualKeyCode::LAlt) {
-                    let (uwidth, uheight) = sys.display.get_framebuffer_dimensions();
+                for _ in 0..ticks {
+                    previous_camera = player.get_camera().clone();
+                    if !cursor_captured {
+                        input.reset_deltas();
+                    }
+                    if let Some((_, recording)) = recording.as_mut() {
+                        recording.record(TICK_DELTA, &input);
+                    }
+                    world.tick(&mut player, &mut actions, &input, TICK_DELTA);
+                    input.reset_deltas();
 
-                    player.get_camera_mut().yaw -=
-                        input.get_mouse_delta_x() as f32 * delta * player.rot_speed;
-                    player.get_camera_mut().pitch +=
-                        input.get_mouse_delta_y() as f32 * delta * player.rot_speed;
+                    if actions.is_active(Action::Quit) {
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    }
+                    if actions.just_activated(Action::ToggleCursor) {
+                        cursor_free = !cursor_free;
+                    }
+                }
 
-                    player.get_camera_mut().pitch = player.get_camera().pitch.min(std::f32::consts::PI - 0.001);
-                    player.get_camera_mut().pitch = player.get_camera().pitch.max(0.0);
+                // Opened containers and crafting tables free the cursor to use their windows
+                let captured = !cursor_free
+                    && player.open_container.is_none()
+                    && player.crafting_table.is_none();
+                if captured != cursor_captured {
+                    cursor_captured = captured;
+                    capture_cursor(&sys.display, &mut sys.imgui, captured);
+                }
 
+                if cursor_captured {
+                    let (uwidth, uheight) = sys.display.get_framebuffer_dimensions();
                     match sys.display.gl_window().window().set_cursor_position(
                         glium::glutin::dpi::PhysicalPosition {
                             x: (uwidth / 2),
@@ -110,12 +184,10 @@ fn main() {
                         Ok(_) => (),
                         Err(e) => println!("Error: {}", e),
                     }
-
-                    input.update_mouse_motion((0.0, 0.0));
                 }
 
-                chunk_loader.update(&player, &sys.display);
-                player.update(delta, &input, &mut chunk_loader);
+                chunk_renderer.update(&world, &player);
+                chunk_renderer.upload(&sys.display);
 
                 let gl_window = sys.display.gl_window();
                 sys.platform
@@ -139,15 +211,22 @@ fn main() {
                 let mut ui = sys.imgui.frame();
 
                 let mut run = true;
-                run_ui(&mut run, &mut ui, fps, delta, &chunk_loader, &player);
+                run_ui(&mut run, &mut ui, fps, delta, timestep.tick_rate(), &world, &chunk_renderer, &player);
+                inventory_ui.draw(&ui, &mut player, &mut world, &registry);
+                ui::draw_hotbar(&ui, &mut player.inventory, &registry.items);
+                console.draw(&ui, &mut world);
+                if ui::draw_settings(&ui, &mut settings, SETTINGS_PATH) {
+                    settings = settings.clone().validated();
+                    apply_settings(&settings, &mut world, &mut chunk_renderer, &mut player, &mut actions, &sys.display);
+                }
                 if !run {
                     *control_flow = ControlFlow::Exit;
                 }
 
+                let lighting = Lighting::at(world.time_of_day());
+                let [r, g, b] = lighting.sky_color;
                 let mut target = sys.display.draw();
-                target.clear_color_and_depth((0.2, 0.6, 0.9, 1.0), 1.0);
-
-                let light = [-0.2, -0.85, 0.5f32];
+                target.clear_color_and_depth((r, g, b, 1.0), 1.0);
 
                 let params = glium::DrawParameters {
                     depth: glium::Depth {
@@ -156,22 +235,42 @@ fn main() {
                         ..Default::default()
                     },
                     backface_culling: glium::draw_parameters::BackfaceCullingMode::CullClockwise,
-                    multisampling: true,
+                    multisampling: settings.msaa > 0,
                     ..Default::default()
                 };
 
-                let perspective =
-                    nalgebra::Matrix4::from(player.get_camera_mut().calculate_projection(&target));
+                // The projection of the player's camera is also used to cull meshes
+                player.get_camera_mut().calculate_projection(&target);
+                let camera = previous_camera.interpolate(player.get_camera(), timestep.alpha());
 
-                chunk_loader.render(
+                chunk_renderer.render(&mut target, &diffuse, &texture_map, &camera, &lighting, &params);
+                chunk_renderer.render_items(
+                    &world,
+                    &sys.display,
                     &mut target,
-                    &diffuse,
-                    perspective
-                        .mul(nalgebra::Matrix4::from(player.get_camera().view_matrix()))
-                        .into(),
-                    light,
+                    &item_shader,
+                    &texture_map,
+                    &camera,
+                    &lighting,
                     &params,
                 );
+                if let Some(hit) = player.target(&world) {
+                    let crack_stage = player
+                        .mining_progress(&world)
+                        .filter(|(pos, _)| *pos == hit.pos)
+                        .map(|(_, fraction)| selection::crack_stage(fraction));
+                    chunk_renderer.render_selection(
+                        &sys.display,
+                        &mut target,
+                        &outline_shader,
+                        &crack_shader,
+                        &texture_map,
+                        &camera,
+                        hit.pos,
+                        crack_stage,
+                        &params,
+                    );
+                }
 
                 let draw_data = ui.render();
                 sys.renderer
@@ -185,30 +284,11 @@ fn main() {
                 device_id: _,
                 ref event,
             } => match event {
-                glutin::event::DeviceEvent::Key(key) => {
-                    input.process_keyboard_event(key.state, key.virtual_keycode.unwrap());
-                    match key.virtual_keycode.as_ref().unwrap() {
-                        VirtualKeyCode::Escape => {
-                            *control_flow = ControlFlow::Exit;
-                            return;
-                        }
-                        VirtualKeyCode::LAlt => match key.state {
-                            Pressed => {
-                                match sys.display.gl_window().window().set_cursor_grab(false) {
-                                    Ok(_) => (),
-                                    Err(e) => println!("Error: {}", e),
-                                }
-                                sys.display.gl_window().window().set_cursor_visible(true);
-                            }
-                            Released => {
-                                match sys.display.gl_window().window().set_cursor_grab(true) {
-                                    Ok(_) => (),
-                                    Err(e) => println!("Error: {}", e),
-                                }
-                                sys.display.gl_window().window().set_cursor_visible(false);
-                            }
-                        },
-                        _ => (),
+                glutin::event::DeviceEvent::Key(key)
+                    if !ui_keyboard || key.state == ElementState::Released =>
+                {
+                    if let Some(keycode) = key.virtual_keycode {
+                        input.process_keyboard_event(key.state, keycode);
                     }
                 }
                 glutin::event::DeviceEvent::MouseMotion { delta } => {
@@ -216,20 +296,29 @@ fn main() {
                 }
                 _ => (),
             },
-            event => {
-                let gl_window = sys.display.gl_window();
-                sys.platform
-                    .handle_event(sys.imgui.io_mut(), gl_window.window(), &event);
+            Event::LoopDestroyed => {
+                if let Some((path, mut recording)) = recording.take() {
+                    recording.finish(&player, &world);
+                    recording.save(&path);
+                }
+                player.close_crafting_grids(&mut world);
+                player.inventory.save(INVENTORY_PATH);
+                world.save_all();
             }
-        });
+            _ => (),
+        }
+    });
 }
 
+#[allow(clippy::too_many_arguments)]
 fn run_ui(
     _run: &mut bool,
     ui: &mut Ui,
     fps: f64,
     delta: f32,
-    loader: &loader::ChunkLoader,
+    tick_rate: f32,
+    world: &world::World,
+    chunk_renderer: &renderer::ChunkRenderer,
     player: &Player,
 ) {
     let window = Window::new("Stats")
@@ -240,6 +329,8 @@ fn run_ui(
     ui.text(format!("FPS: {}", 1.0 / delta));
     ui.text(format!("Avg delta (ms): {}", 1000.0 / fps));
     ui.text(format!("Delta (ms): {}", delta * 1000.0));
+    ui.text(format!("Tick rate: {:.1}/{} Hz", tick_rate, TICK_RATE));
+    ui.text(format!("Time: {}", daylight::format_time_of_day(world.time_of_day())));
     ui.new_line();
     ui.text(format!(
         "Player: ({:.3}, {:.3}, {:.3})",
@@ -250,27 +341,138 @@ fn run_ui(
     ));
     ui.text(format!(
         "Number of chunks loaded: {}",
-        loader.get_number_of_loaded_chunks()
+        world.loader().get_number_of_loaded_chunks()
     ));
     ui.text(format!(
         "Number of meshes loaded: {}",
-        loader.get_number_of_loaded_meshes()
+        chunk_renderer.get_number_of_loaded_meshes()
+    ));
+    ui.text(format!(
+        "Cancelled jobs: {} chunks, {} meshes",
+        world.loader().get_number_of_cancelled_chunks(),
+        chunk_renderer.get_number_of_cancelled_meshes()
+    ));
+    ui.text(format!(
+        "Outdated meshes: {}",
+        chunk_renderer.get_number_of_outdated_meshes()
     ));
+    for (name, stats) in [
+        ("Chunk", world.loader().chunk_cache_stats()),
+        ("Mesh", chunk_renderer.mesh_cache_stats()),
+    ] {
+        ui.text(format!(
+            "{} cache: {} hits, {} misses, {} entries, {:.1}/{:.1} MiB",
+            name,
+            stats.hits,
+            stats.misses,
+            stats.entries,
+            stats.bytes as f64 / (1024.0 * 1024.0),
+            stats.budget as f64 / (1024.0 * 1024.0)
+        ));
+    }
+    for (name, workers) in [
+        ("Chunk", world.loader().worker_stats()),
+        ("Mesh", chunk_renderer.worker_stats()),
+    ] {
+        let utilisation: Vec<String> = workers
+            .iter()
+            .map(|stats| format!("{:.0}%", stats.utilisation * 100.0))
+            .collect();
+        ui.text(format!(
+            "{} workers: {} ({} jobs, {} panics)",
+            name,
+            utilisation.join(" "),
+            workers.iter().map(|stats| stats.jobs).sum::<u64>(),
+            workers.iter().map(|stats| stats.panics).sum::<u64>()
+        ));
+    }
     tok.end();
 }
 
-fn init() -> System {
+/// Plays a recording back without a window and compares the outcome with the recorded one
+fn replay(path: &str) {
+    let recording = match Recording::load(path) {
+        Some(recording) => recording,
+        None => {
+            println!("Error loading recording {}", path);
+            std::process::exit(1);
+        }
+    };
+    clear_dir(RECORDING_SAVE_DIR);
+    let registry = Arc::new(registry::Registry::load("res"));
+    let outcome = recording.replay(registry, RECORDING_SAVE_DIR);
+    match recording.outcome {
+        Some(expected) if expected == outcome => {
+            println!("Replayed {} ticks, outcome matches: {:?}", recording.ticks.len(), outcome);
+        }
+        Some(expected) => {
+            println!("Replay differs from recording: expected {:?}, got {:?}", expected, outcome);
+            std::process::exit(1);
+        }
+        None => println!("Recording has no outcome, replay ended with {:?}", outcome),
+    }
+}
+
+/// Removes a directory and everything in it, if it exists
+fn clear_dir(dir: &str) {
+    match std::fs::remove_dir_all(dir) {
+        Ok(_) => (),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+        Err(e) => println!("Error removing {}: {}", dir, e),
+    }
+}
+
+/// Applies the settings that can be changed while the game runs
+fn apply_settings(
+    settings: &Settings,
+    world: &mut world::World,
+    chunk_renderer: &mut renderer::ChunkRenderer,
+    player: &mut Player,
+    actions: &mut Actions,
+    display: &glium::Display,
+) {
+    world.set_load_distance(settings.load_distance());
+    chunk_renderer.set_render_distance(settings.render_distance);
+    world.set_worker_threads(settings.chunk_threads());
+    chunk_renderer.set_worker_threads(settings.mesh_threads());
+    chunk_renderer.set_anisotropy(settings.anisotropy);
+    player.rot_speed = settings.rot_speed;
+    player.get_camera_mut().fov = settings.fov_radians();
+    actions.set_bindings(settings.bindings.clone());
+
+    let size = glium::glutin::dpi::PhysicalSize {
+        width: settings.window_width,
+        height: settings.window_height,
+    };
+    let gl_window = display.gl_window();
+    if gl_window.window().inner_size() != size {
+        gl_window.window().set_inner_size(size);
+    }
+}
+
+/// Hides the cursor and keeps it in the window to look around, or frees it to use the menus.
+/// imgui ignores the captured cursor, which stays in the middle of the window
+fn capture_cursor(display: &glium::Display, imgui: &mut Context, captured: bool) {
+    match display.gl_window().window().set_cursor_grab(captured) {
+        Ok(_) => (),
+        Err(e) => println!("Error: {}", e),
+    }
+    display.gl_window().window().set_cursor_visible(!captured);
+    imgui.io_mut().config_flags.set(ConfigFlags::NO_MOUSE, captured);
+}
+
+fn init(settings: &Settings) -> System {
     let event_loop = EventLoop::new();
     let wb = glutin::window::WindowBuilder::new()
         .with_title("Vixen")
         .with_inner_size(glium::glutin::dpi::PhysicalSize {
-            width: 1920,
-            height: 1080,
+            width: settings.window_width,
+            height: settings.window_height,
         })
         .with_position(glium::glutin::dpi::PhysicalPosition { x: 0, y: 0 });
     let cb = glutin::ContextBuilder::new()
         .with_depth_buffer(24)
-        .with_multisampling(16);
+        .with_multisampling(settings.msaa);
     let display = glium::Display::new(wb, cb, &event_loop).unwrap();
 
     // IMGUI
@@ -1,21 +1,31 @@
-use std::time::Instant;
-
+use crate::actions::{Action, Actions};
 use crate::camera;
 use crate::chunk::Block;
-use crate::input;
-use crate::loader;
-use crate::loader::ChunkLoader;
-
-use glium::glutin;
+use crate::crafting::CraftingGrid;
+use crate::input::Input;
+use crate::inventory::{Inventory, ItemStack, HOTBAR_SIZE};
+use crate::pos::BlockPos;
+use crate::raycast::RayHit;
+use crate::world::World;
 
 use parry3d::bounding_volume::BoundingVolume;
 use parry3d::bounding_volume::AABB;
 use parry3d::na::Point3;
 
+use serde::{Deserialize, Serialize};
+
+/// Seconds the mine button can be released before the mining progress is lost
+const MINE_RESET_TIME: f32 = 0.08;
+/// Seconds between placing two blocks while the place button is held
+const BUILD_INTERVAL: f32 = 0.2;
+/// Distance in blocks the player can mine and place blocks at
+const REACH: f32 = 4.0;
+
 pub struct Player {
-    pub x: f32,
-    pub y: f32,
-    pub z: f32,
+    /// Position of the player's feet in world space, in double precision like the camera
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
 
     pub velocity: (f32, f32, f32),
 
@@ -23,14 +33,35 @@ pub struct Player {
     pub rot_speed: f32,
     pub jump_power: f32,
     pub camera: camera::Camera,
+    pub inventory: Inventory,
+    /// Crafting grid that is part of the inventory
+    pub crafting_grid: CraftingGrid,
+    /// Crafting grid of the crafting table the player is using, if any
+    pub crafting_table: Option<CraftingGrid>,
+    /// Position of the block entity the player has opened, if any
+    pub open_container: Option<BlockPos>,
 
     falling: bool,
     miner_builder: MinerBuilder,
 }
 
+/// State of the player that decides how it moves and what it can build, saved at the start of recordings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayerState {
+    pub position: [f64; 3],
+    pub velocity: (f32, f32, f32),
+    pub yaw: f32,
+    pub pitch: f32,
+    pub lin_speed: f32,
+    pub rot_speed: f32,
+    pub jump_power: f32,
+    pub falling: bool,
+    pub inventory: Inventory,
+}
+
 impl Player {
     pub fn new(
-        (x, y, z): (f32, f32, f32),
+        (x, y, z): (f64, f64, f64),
         lin_speed: f32,
         rot_speed: f32,
         jump_power: f32,
@@ -50,48 +81,134 @@ impl Player {
         }
     }
 
-    pub fn update(&mut self, delta: f32, input: &input::Input, loader: &mut loader::ChunkLoader) {
+    /// Creates a player from a saved state
+    pub fn from_state(state: PlayerState) -> Player {
+        let [x, y, z] = state.position;
+        let mut player = Player {
+            x,
+            y,
+            z,
+            velocity: state.velocity,
+            lin_speed: state.lin_speed,
+            rot_speed: state.rot_speed,
+            jump_power: state.jump_power,
+            falling: state.falling,
+            inventory: state.inventory,
+            ..Default::default()
+        };
+        player.camera.yaw = state.yaw;
+        player.camera.pitch = state.pitch;
+        player
+    }
+
+    /// Returns the state of the player, which `from_state` creates the same player from
+    pub fn state(&self) -> PlayerState {
+        PlayerState {
+            position: [self.x, self.y, self.z],
+            velocity: self.velocity,
+            yaw: self.camera.yaw,
+            pitch: self.camera.pitch,
+            lin_speed: self.lin_speed,
+            rot_speed: self.rot_speed,
+            jump_power: self.jump_power,
+            falling: self.falling,
+            inventory: self.inventory.clone(),
+        }
+    }
+
+    /// Returns the block the player is looking at within reach. Fluids are looked through
+    pub fn target(&self, world: &World) -> Option<RayHit> {
+        let eye = [self.camera.x, self.camera.y, self.camera.z];
+        world.raycast(eye, self.camera.direction(), REACH, |info| !info.fluid)
+    }
+
+    /// Returns the block being mined and the fraction of it that is mined, from 0 to 1
+    pub fn mining_progress(&self, world: &World) -> Option<(BlockPos, f32)> {
+        let miner = &self.miner_builder;
+        if miner.mining_progress <= 0.0 || miner.time - miner.last_mine_time > MINE_RESET_TIME {
+            return None;
+        }
+        let block = world.get_block(miner.coord)?;
+        let hardness = world.registry().blocks.get(block.id())?.hardness;
+        Some((miner.coord, miner.mining_progress / hardness))
+    }
+
+    /// Turns the camera by the mouse movement of the last tick
+    pub fn look(&mut self, input: &Input, delta: f32) {
+        self.camera.yaw -= input.get_mouse_delta_x() as f32 * delta * self.rot_speed;
+        self.camera.pitch += input.get_mouse_delta_y() as f32 * delta * self.rot_speed;
+        self.camera.pitch = self.camera.pitch.clamp(0.0, std::f32::consts::PI - 0.001);
+    }
+
+    pub fn update(&mut self, delta: f32, actions: &Actions, world: &mut World) {
         let mut step = (0.0, 0.0, 0.0);
+        self.miner_builder.time += delta;
 
-        if input.is_key_pressed(&glutin::event::VirtualKeyCode::W) {
+        if actions.is_active(Action::MoveForward) {
             step.2 += self.lin_speed * self.camera.yaw.sin() * delta;
             step.0 += self.lin_speed * self.camera.yaw.cos() * delta;
         }
-        if input.is_key_pressed(&glutin::event::VirtualKeyCode::S) {
+        if actions.is_active(Action::MoveBack) {
             step.2 -= self.lin_speed * self.camera.yaw.sin() * delta;
             step.0 -= self.lin_speed * self.camera.yaw.cos() * delta;
         }
-        if input.is_key_pressed(&glutin::event::VirtualKeyCode::A) {
+        if actions.is_active(Action::MoveLeft) {
             step.2 += self.lin_speed * self.camera.yaw.cos() * delta;
             step.0 -= self.lin_speed * self.camera.yaw.sin() * delta;
         }
-        if input.is_key_pressed(&glutin::event::VirtualKeyCode::D) {
+        if actions.is_active(Action::MoveRight) {
             step.2 -= self.lin_speed * self.camera.yaw.cos() * delta;
             step.0 += self.lin_speed * self.camera.yaw.sin() * delta;
         }
 
-        if input.is_key_pressed(&glutin::event::VirtualKeyCode::Space) && !self.falling {
+        // Scrolling up selects the slot to the left, wrapping around at the ends of the hotbar
+        let scroll = actions.scroll_steps();
+        if scroll != 0 {
+            let slot = (self.inventory.selected() as i32 - scroll).rem_euclid(HOTBAR_SIZE as i32);
+            self.inventory.select(slot as usize);
+        }
+        for (slot, action) in Action::HOTBAR.into_iter().enumerate().take(HOTBAR_SIZE) {
+            if actions.is_active(action) {
+                self.inventory.select(slot);
+            }
+        }
+
+        if actions.is_active(Action::Jump) && !self.falling {
             self.velocity.1 = self.jump_power;
             self.falling = true;
         }
 
         // Check if player is trying to mine
-        if input.is_mouse_button_pressed(&glutin::event::MouseButton::Left) {
-            let range = 4.0;
-            let coord = cast_ray([self.camera.x,self.camera.y,self.camera.z], range, self.camera.pitch, self.camera.yaw, loader);
-            mine(&mut self.miner_builder, coord, delta, 10.0, loader);
+        if actions.is_active(Action::Mine) {
+            if let Some(hit) = self.target(world) {
+                mine(&mut self.miner_builder, &mut self.inventory, hit.pos, delta, world);
+            }
         }
 
-        // Check if player is trying to build
-        if input.is_mouse_button_pressed(&glutin::event::MouseButton::Right) {
-            if self.miner_builder.can_build() {
-                let range = 4.0;
-                let coord = cast_ray_in_front([self.camera.x, self.camera.y, self.camera.z], range, self.camera.pitch, self.camera.yaw, loader);
-                if let Some(coord) = coord {
-                    if coord != [self.camera.x.floor() as i32, self.camera.y.floor() as i32, self.camera.z.floor() as i32]
-                    && coord != [self.camera.x.floor() as i32, self.camera.y.floor() as i32 - 1, self.camera.z.floor() as i32] {
-                        loader.set_block(coord, Block::new(1, 5.0));
-                    }
+        // Check if player is trying to interact with a block or build
+        let placing = actions.is_active(Action::Place) && self.miner_builder.can_build();
+        if let Some(hit) = placing.then(|| self.target(world)).flatten() {
+            let crafting_grid = world
+                .get_block(hit.pos)
+                .and_then(|block| world.registry().blocks.get(block.id()))
+                .and_then(|info| info.crafting_grid);
+            let block_id = self
+                .inventory
+                .selected_stack()
+                .and_then(|stack| world.registry().items.get(stack.id()))
+                .and_then(|info| info.block);
+
+            if world.has_block_entity(hit.pos) {
+                self.open_container = Some(hit.pos);
+            } else if let Some(size) = crafting_grid {
+                if self.crafting_table.is_none() {
+                    self.crafting_table = Some(CraftingGrid::new(size));
+                }
+            } else if let (Some(block_id), Some(coord)) = (block_id, hit.adjacent()) {
+                let head = BlockPos::containing([self.camera.x, self.camera.y, self.camera.z]);
+                if coord != head && coord != head.offset(0, -1, 0) {
+                    world.set_block(coord, Block::new(block_id));
+                    self.inventory.remove(self.inventory.selected(), 1);
                 }
             }
         }
@@ -102,17 +219,46 @@ impl Player {
         step.1 += self.velocity.1 * delta;
         step.2 += self.velocity.2 * delta;
 
-        step = self.collide(delta, loader, step);
+        step = self.collide(delta, world, step);
 
-        self.x += step.0;
-        self.y += step.1;
-        self.z += step.2;
+        self.x += step.0 as f64;
+        self.y += step.1 as f64;
+        self.z += step.2 as f64;
 
         self.camera.x = self.x;
         self.camera.y = self.y + 1.5;
         self.camera.z = self.z;
     }
 
+    /// Closes the crafting table, moving the items in it back into the inventory
+    pub fn close_crafting_table(&mut self, world: &mut World) {
+        if let Some(mut grid) = self.crafting_table.take() {
+            self.give(grid.clear(), world);
+        }
+    }
+
+    /// Moves the items in every crafting grid back into the inventory
+    pub fn close_crafting_grids(&mut self, world: &mut World) {
+        self.close_crafting_table(world);
+        let stacks = self.crafting_grid.clear();
+        self.give(stacks, world);
+    }
+
+    /// Adds stacks to the inventory, dropping the items that don't fit into the world
+    fn give(&mut self, stacks: Vec<ItemStack>, world: &mut World) {
+        for stack in stacks {
+            if let Some(leftover) = self.inventory.add(stack) {
+                world.spawn_item([self.x as f32, self.y as f32 + 1.0, self.z as f32], leftover);
+            }
+        }
+    }
+
+    /// Returns the bounding box of the player
+    pub fn aabb(&self) -> AABB {
+        let position = (self.x as f32, self.y as f32, self.z as f32);
+        create_player_aabb(position, position)
+    }
+
     pub fn get_camera(&self) -> &camera::Camera {
         &self.camera
     }
@@ -124,23 +270,30 @@ impl Player {
     fn collide(
         &mut self,
         delta: f32,
-        loader: &loader::ChunkLoader,
+        world: &World,
         (dx, dy, dz): (f32, f32, f32),
     ) -> (f32, f32, f32) {
         let (mut dx, mut dy, mut dz) = (dx, dy, dz);
-        let (nx, ny, nz) = (self.x + dx, self.y + dy, self.z + dz);
+        // Boxes are relative to the block the player is in, so collisions stay precise far from the origin
+        let origin = BlockPos::containing([self.x, self.y, self.z]);
+        let (px, py, pz) = (
+            (self.x - origin.x as f64) as f32,
+            (self.y - origin.y as f64) as f32,
+            (self.z - origin.z as f64) as f32,
+        );
+        let (nx, ny, nz) = (px + dx, py + dy, pz + dz);
 
         let player_box_current =
-            create_player_aabb((self.x, self.y, self.z), (self.x, self.y, self.z));
+            create_player_aabb((px, py, pz), (px, py, pz));
         let player_box_stepped = create_player_aabb(
-            (self.x.min(nx), self.y.min(ny), self.z.min(nz)),
-            (self.x.max(nx), self.y.max(ny), self.z.max(nz)),
+            (px.min(nx), py.min(ny), pz.min(nz)),
+            (px.max(nx), py.max(ny), pz.max(nz)),
         );
 
         for x in (nx.floor() as i32 - 1)..(nx.floor() as i32 + 2) {
             for y in (ny.floor() as i32 - 1)..(ny.floor() as i32 + 3) {
                 for z in (nz.floor() as i32 - 1)..(nz.floor() as i32 + 2) {
-                    match loader.get_block([x, y, z]) {
+                    match world.get_block(origin.offset(x, y, z)) {
                         None => {
                             self.velocity.1 = 0.0;
                             dy = 0.0;
@@ -154,16 +307,16 @@ impl Player {
                                 && !player_box_current.intersects(&block_aabb)
                             {
                                 let x_box = create_player_aabb(
-                                    (self.x.min(nx), self.y, self.z),
-                                    (self.x.max(nx), self.y, self.z),
+                                    (px.min(nx), py, pz),
+                                    (px.max(nx), py, pz),
                                 );
                                 let y_box = create_player_aabb(
-                                    (self.x, self.y.min(ny), self.z),
-                                    (self.x, self.y.max(ny), self.z),
+                                    (px, py.min(ny), pz),
+                                    (px, py.max(ny), pz),
                                 );
                                 let z_box = create_player_aabb(
-                                    (self.x, self.y, self.z.min(nz)),
-                                    (self.x, self.y, self.z.max(nz)),
+                                    (px, py, pz.min(nz)),
+                                    (px, py, pz.max(nz)),
                                 );
 
                                 if x_box.intersects(&block_aabb) {
@@ -211,8 +364,13 @@ impl Default for Player {
                 pitch: std::f32::consts::PI / 2.0,
                 yaw: 0.0,
                 roll: 0.0,
+                fov: camera::DEFAULT_FOV,
                 projection: [[0.0; 4]; 4],
             },
+            inventory: Inventory::default(),
+            crafting_grid: CraftingGrid::new(2),
+            crafting_table: None,
+            open_container: None,
             miner_builder: MinerBuilder::default(),
         }
     }
@@ -233,98 +391,73 @@ fn create_player_aabb(
     )
 }
 
-fn cast_ray(start_point: [f32;3], rho: f32, phi: f32, theta: f32, loader: &ChunkLoader) -> [i32;3] {
-    let ((sin_p, cos_p), (sin_t, cos_t)) = (phi.sin_cos(), theta.sin_cos());
-    let ray_size = [rho * sin_p * cos_t,
-                             rho * cos_p,
-                             rho * sin_p * sin_t];
-
-    let end_point = (start_point[0] + ray_size[0], start_point[1] + ray_size[1], start_point[2] + ray_size[2]);
-
-    for (x, y, z) in line_drawing::WalkVoxels::new((start_point[0], start_point[1], start_point[2]), end_point, &line_drawing::VoxelOrigin::Corner) {
-        if let Some(block) = loader.get_block([x,y,z]) {
-            if !block.is_air() {
-                return [x,y,z];
-            }
-        }
-    }
-    [start_point[0].floor() as i32, start_point[1].floor() as i32, start_point[2].floor() as i32]
-}
-
-/// Casts a ray and returns block coordinate of the air block in front of the block the ray hit, and None otherwise
-fn cast_ray_in_front(start_point: [f32;3], rho: f32, phi: f32, theta: f32, loader: &ChunkLoader) -> Option<[i32;3] >{
-    let ((sin_p, cos_p), (sin_t, cos_t)) = (phi.sin_cos(), theta.sin_cos());
-    let ray_size = [rho * sin_p * cos_t,
-                             rho * cos_p,
-                             rho * sin_p * sin_t];
-
-    let end_point = (start_point[0] + ray_size[0], start_point[1] + ray_size[1], start_point[2] + ray_size[2]);
-    let mut last = [start_point[0].floor() as i32, start_point[1].floor() as i32, start_point[2].floor() as i32];
-    for (x, y, z) in line_drawing::WalkVoxels::new((start_point[0], start_point[1], start_point[2]), end_point, &line_drawing::VoxelOrigin::Corner) {
-        if let Some(block) = loader.get_block([x,y,z]) {
-            if block.id() != 0 {
-                return Some(last);
-            }
-        }
-        last = [x,y,z];
-    }
-    None
-}
-
 #[inline]
-fn mine(miner: &mut MinerBuilder, coord: [i32;3], delta: f32, speed: f32, loader: &mut ChunkLoader) {
+fn mine(miner: &mut MinerBuilder, inventory: &mut Inventory, coord: BlockPos, delta: f32, world: &mut World) {
     if miner.coord != coord {
         miner.reset_miner(coord);
     }
     miner.coord = coord;
     miner.update();
-    let block = loader.get_block(coord).unwrap_or(Block::air());
-    let health = block.health();
-    miner.mining_progress += delta * speed;
-    if health - miner.mining_progress <= 0.0 && !block.is_air() {
-        loader.set_block(coord.clone(), Block::air());
-        
+    let block = world.get_block(coord).unwrap_or(Block::air());
+    let registry = world.registry();
+    let info = match registry.blocks.get(block.id()) {
+        None => return,
+        Some(info) => info,
+    };
+    let tool = inventory
+        .selected_stack()
+        .and_then(|stack| registry.items.get(stack.id()))
+        .and_then(|item| item.tool.as_ref());
+
+    miner.mining_progress += delta * info.mining_speed(tool);
+    if miner.mining_progress >= info.hardness {
+        let mut drops: Vec<ItemStack> = registry
+            .blocks
+            .drop(block.id(), tool)
+            .and_then(|id| registry.items.stack(id, 1))
+            .into_iter()
+            .collect();
+        if tool.is_some() {
+            inventory.damage_selected(1);
+        }
+        if let Some(mut entity) = world.set_block(coord, Block::air()) {
+            drops.extend(entity.take_contents());
+        }
+        for stack in drops {
+            world.spawn_item(coord.center(), stack);
+        }
+        miner.reset_miner(coord);
     }
 }
 
+/// Times are in seconds of game time, counted from the ticks so they are the same when input is replayed
+#[derive(Default)]
 struct MinerBuilder {
     pub mining_progress: f32,
-    coord: [i32;3],
-    last_mine_time: Instant,
-    last_build_time: Instant,
-}
-
-impl Default for MinerBuilder {
-    fn default() -> Self {
-        Self {
-            mining_progress: Default::default(),
-            coord: Default::default(),
-            last_mine_time: Instant::now(),
-            last_build_time: Instant::now()
-        }
-    }
+    coord: BlockPos,
+    time: f32,
+    last_mine_time: f32,
+    last_build_time: f32,
 }
 
 impl MinerBuilder {
-    pub fn reset_miner(&mut self, coord: [i32;3]) {
+    pub fn reset_miner(&mut self, coord: BlockPos) {
         self.mining_progress = 0.0;
         self.coord = coord;
     } 
 
     pub fn can_build(&mut self) -> bool {
-        let now = Instant::now();
-        if (now - self.last_build_time).as_millis() > 200 {
-            self.last_build_time = now;
+        if self.time - self.last_build_time > BUILD_INTERVAL {
+            self.last_build_time = self.time;
             return true;
         }
         false
     }
 
     pub fn update(&mut self) {
-        let now = Instant::now();
-        if (now - self.last_mine_time).as_millis() > 80 {
+        if self.time - self.last_mine_time > MINE_RESET_TIME {
             self.mining_progress = 0.0;
         }
-        self.last_mine_time = now;
+        self.last_mine_time = self.time;
     }
 }
\ No newline at end of file
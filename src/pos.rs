@@ -0,0 +1,366 @@
+use std::ops::{Add, Sub};
+
+use serde::{Deserialize, Serialize};
+
+use crate::chunk::CHUNK_SIZE;
+
+/// Offsets to the 6 neighbours of a position, in the order of the block faces:
+/// right (+x), left (-x), bottom (-y), top (+y), front (+z), back (-z)
+pub const NEIGHBOR_OFFSETS: [[i32; 3]; 6] = [
+    [1, 0, 0],
+    [-1, 0, 0],
+    [0, -1, 0],
+    [0, 1, 0],
+    [0, 0, 1],
+    [0, 0, -1],
+];
+
+const CHUNK_SIZE_I32: [i32; 3] = [CHUNK_SIZE.0 as i32, CHUNK_SIZE.1 as i32, CHUNK_SIZE.2 as i32];
+
+/// Position of a block in world space. Arithmetic wraps around at the limits of `i32`, so the blocks past
+/// the edge of the world are the ones on the other side instead of a panic
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockPos {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl BlockPos {
+    pub const fn new(x: i32, y: i32, z: i32) -> BlockPos {
+        BlockPos { x, y, z }
+    }
+
+    /// Returns the block containing a point in world space, given in single or double precision
+    pub fn containing<T: Into<f64>>([x, y, z]: [T; 3]) -> BlockPos {
+        let [x, y, z] = [x.into(), y.into(), z.into()];
+        BlockPos::new(x.floor() as i32, y.floor() as i32, z.floor() as i32)
+    }
+
+    /// Returns the block at `local` inside of chunk `chunk`
+    pub fn from_parts(chunk: ChunkPos, local: LocalPos) -> BlockPos {
+        chunk.origin() + BlockPos::new(local.x as i32, local.y as i32, local.z as i32)
+    }
+
+    /// Returns the chunk containing the block
+    pub fn chunk(self) -> ChunkPos {
+        ChunkPos::new(
+            self.x.div_euclid(CHUNK_SIZE_I32[0]),
+            self.y.div_euclid(CHUNK_SIZE_I32[1]),
+            self.z.div_euclid(CHUNK_SIZE_I32[2]),
+        )
+    }
+
+    /// Returns the position of the block inside of its chunk
+    pub fn local(self) -> LocalPos {
+        LocalPos {
+            x: self.x.rem_euclid(CHUNK_SIZE_I32[0]) as usize,
+            y: self.y.rem_euclid(CHUNK_SIZE_I32[1]) as usize,
+            z: self.z.rem_euclid(CHUNK_SIZE_I32[2]) as usize,
+        }
+    }
+
+    /// Splits the position into its chunk and the position inside of the chunk
+    pub fn split(self) -> (ChunkPos, LocalPos) {
+        (self.chunk(), self.local())
+    }
+
+    pub fn offset(self, dx: i32, dy: i32, dz: i32) -> BlockPos {
+        BlockPos::new(
+            self.x.wrapping_add(dx),
+            self.y.wrapping_add(dy),
+            self.z.wrapping_add(dz),
+        )
+    }
+
+    /// Returns the 6 blocks sharing a face with this one, in the order of `NEIGHBOR_OFFSETS`
+    pub fn neighbors(self) -> [BlockPos; 6] {
+        NEIGHBOR_OFFSETS.map(|[dx, dy, dz]| self.offset(dx, dy, dz))
+    }
+
+    /// Returns the point at the center of the block
+    pub fn center(self) -> [f32; 3] {
+        [self.x as f32 + 0.5, self.y as f32 + 0.5, self.z as f32 + 0.5]
+    }
+}
+
+impl Add for BlockPos {
+    type Output = BlockPos;
+
+    fn add(self, other: BlockPos) -> BlockPos {
+        self.offset(other.x, other.y, other.z)
+    }
+}
+
+impl Sub for BlockPos {
+    type Output = BlockPos;
+
+    fn sub(self, other: BlockPos) -> BlockPos {
+        BlockPos::new(
+            self.x.wrapping_sub(other.x),
+            self.y.wrapping_sub(other.y),
+            self.z.wrapping_sub(other.z),
+        )
+    }
+}
+
+impl From<[i32; 3]> for BlockPos {
+    fn from([x, y, z]: [i32; 3]) -> BlockPos {
+        BlockPos::new(x, y, z)
+    }
+}
+
+/// Position of a chunk in chunk space. Arithmetic wraps around like it does for `BlockPos`
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkPos {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl ChunkPos {
+    pub const fn new(x: i32, y: i32, z: i32) -> ChunkPos {
+        ChunkPos { x, y, z }
+    }
+
+    /// Returns the chunk containing a point in world space, given in single or double precision
+    pub fn containing<T: Into<f64>>(point: [T; 3]) -> ChunkPos {
+        BlockPos::containing(point).chunk()
+    }
+
+    /// Returns the block in the chunk with the lowest coordinates
+    pub fn origin(self) -> BlockPos {
+        BlockPos::new(
+            self.x.wrapping_mul(CHUNK_SIZE_I32[0]),
+            self.y.wrapping_mul(CHUNK_SIZE_I32[1]),
+            self.z.wrapping_mul(CHUNK_SIZE_I32[2]),
+        )
+    }
+
+    pub fn offset(self, dx: i32, dy: i32, dz: i32) -> ChunkPos {
+        ChunkPos::new(
+            self.x.wrapping_add(dx),
+            self.y.wrapping_add(dy),
+            self.z.wrapping_add(dz),
+        )
+    }
+
+    /// Returns the 6 chunks sharing a face with this one, in the order of `NEIGHBOR_OFFSETS`
+    pub fn neighbors(self) -> [ChunkPos; 6] {
+        NEIGHBOR_OFFSETS.map(|[dx, dy, dz]| self.offset(dx, dy, dz))
+    }
+
+    /// Returns the largest difference along a single axis to `other`
+    pub fn axis_distance(self, other: ChunkPos) -> u32 {
+        self.x
+            .abs_diff(other.x)
+            .max(self.y.abs_diff(other.y))
+            .max(self.z.abs_diff(other.z))
+    }
+}
+
+impl Add for ChunkPos {
+    type Output = ChunkPos;
+
+    fn add(self, other: ChunkPos) -> ChunkPos {
+        self.offset(other.x, other.y, other.z)
+    }
+}
+
+impl Sub for ChunkPos {
+    type Output = ChunkPos;
+
+    fn sub(self, other: ChunkPos) -> ChunkPos {
+        ChunkPos::new(
+            self.x.wrapping_sub(other.x),
+            self.y.wrapping_sub(other.y),
+            self.z.wrapping_sub(other.z),
+        )
+    }
+}
+
+/// Position of a block inside of a chunk. Always within `CHUNK_SIZE`.
+/// Saved in the same format as the `(usize, usize, usize)` tuples it replaced
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LocalPos {
+    x: usize,
+    y: usize,
+    z: usize,
+}
+
+impl LocalPos {
+    /// Returns none if the position is outside of a chunk
+    pub fn new(x: usize, y: usize, z: usize) -> Option<LocalPos> {
+        if x < CHUNK_SIZE.0 && y < CHUNK_SIZE.1 && z < CHUNK_SIZE.2 {
+            Some(LocalPos { x, y, z })
+        } else {
+            None
+        }
+    }
+
+    pub fn x(self) -> usize {
+        self.x
+    }
+
+    pub fn y(self) -> usize {
+        self.y
+    }
+
+    pub fn z(self) -> usize {
+        self.z
+    }
+
+    /// Returns the index of the block in a chunk's block array
+    pub fn index(self) -> [usize; 3] {
+        [self.x, self.y, self.z]
+    }
+
+    /// Returns the neighbour at `offset` if it is in the same chunk
+    pub fn offset(self, [dx, dy, dz]: [i32; 3]) -> Option<LocalPos> {
+        let coord = |c: usize, d: i32| usize::try_from(c as i32 + d).ok();
+        LocalPos::new(coord(self.x, dx)?, coord(self.y, dy)?, coord(self.z, dz)?)
+    }
+
+    /// Returns which faces of the chunk the position is on, in the order of `NEIGHBOR_OFFSETS`
+    pub fn on_border(self) -> [bool; 6] {
+        [
+            self.x == CHUNK_SIZE.0 - 1,
+            self.x == 0,
+            self.y == 0,
+            self.y == CHUNK_SIZE.1 - 1,
+            self.z == CHUNK_SIZE.2 - 1,
+            self.z == 0,
+        ]
+    }
+
+    /// Iterates over every position in a chunk
+    pub fn all() -> impl Iterator<Item = LocalPos> {
+        (0..CHUNK_SIZE.0).flat_map(|x| {
+            (0..CHUNK_SIZE.1).flat_map(move |y| {
+                (0..CHUNK_SIZE.2).map(move |z| LocalPos { x, y, z })
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EDGES: [i32; 12] = [
+        i32::MIN,
+        i32::MIN + 1,
+        i32::MIN + 31,
+        i32::MIN + 32,
+        -33,
+        -32,
+        -31,
+        -1,
+        0,
+        31,
+        32,
+        i32::MAX,
+    ];
+
+    #[test]
+    fn negative_blocks_belong_to_lower_chunk() {
+        assert_eq!(BlockPos::new(-1, -1, -1).chunk(), ChunkPos::new(-1, -1, -1));
+        assert_eq!(BlockPos::new(-1, -1, -1).local(), LocalPos::new(31, 31, 31).unwrap());
+        assert_eq!(BlockPos::new(-32, 0, 31).chunk(), ChunkPos::new(-1, 0, 0));
+        assert_eq!(BlockPos::new(-32, 0, 31).local(), LocalPos::new(0, 0, 31).unwrap());
+        assert_eq!(BlockPos::new(-33, 32, 0).chunk(), ChunkPos::new(-2, 1, 0));
+        assert_eq!(BlockPos::new(-33, 32, 0).local(), LocalPos::new(31, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn split_and_join_round_trip() {
+        let xs = (-70..70).chain(EDGES);
+        for x in xs {
+            for &y in EDGES.iter() {
+                for &z in EDGES.iter() {
+                    let pos = BlockPos::new(x, y, z);
+                    let (chunk, local) = pos.split();
+                    assert_eq!(BlockPos::from_parts(chunk, local), pos, "{:?}", pos);
+                    let origin = chunk.origin();
+                    for (o, p) in [(origin.x, pos.x), (origin.y, pos.y), (origin.z, pos.z)] {
+                        assert!(o <= p && p - o < 32, "{:?}", pos);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn extreme_chunks() {
+        let (min, max) = (BlockPos::new(i32::MIN, i32::MIN, i32::MIN), BlockPos::new(i32::MAX, i32::MAX, i32::MAX));
+        let (min_chunk, max_chunk) = (i32::MIN / 32, i32::MAX / 32);
+        assert_eq!(min.chunk(), ChunkPos::new(min_chunk, min_chunk, min_chunk));
+        assert_eq!(min.local(), LocalPos::new(0, 0, 0).unwrap());
+        assert_eq!(max.chunk(), ChunkPos::new(max_chunk, max_chunk, max_chunk));
+        assert_eq!(max.local(), LocalPos::new(31, 31, 31).unwrap());
+        assert_eq!(min.chunk().origin(), min);
+        assert_eq!(max.chunk().origin(), max.offset(-31, -31, -31));
+    }
+
+    #[test]
+    fn arithmetic_wraps_at_the_edge_of_the_world() {
+        let (min, max) = (BlockPos::new(i32::MIN, i32::MIN, i32::MIN), BlockPos::new(i32::MAX, i32::MAX, i32::MAX));
+        assert_eq!(max.offset(1, 1, 1), min);
+        assert_eq!(min.offset(-1, -1, -1), max);
+        assert_eq!(max + BlockPos::new(1, 1, 1), min);
+        assert_eq!(min - BlockPos::new(1, 1, 1), max);
+        for pos in [min, max, BlockPos::new(i32::MIN, i32::MAX, 0)] {
+            for (neighbor, offset) in pos.neighbors().iter().zip(NEIGHBOR_OFFSETS) {
+                assert_eq!(*neighbor - pos, BlockPos::from(offset));
+            }
+        }
+
+        let chunk = ChunkPos::new(i32::MAX, i32::MIN, i32::MAX);
+        assert_eq!(chunk.neighbors()[0], ChunkPos::new(i32::MIN, i32::MIN, i32::MAX));
+        assert_eq!(chunk.neighbors()[2], ChunkPos::new(i32::MAX, i32::MAX, i32::MAX));
+        assert_eq!(chunk.neighbors()[4] - chunk, ChunkPos::new(0, 0, 1));
+        // Chunks past the last one are never reached, but their origins don't panic either
+        assert_eq!(ChunkPos::new(i32::MAX / 32 + 1, 0, 0).origin().x, i32::MIN);
+    }
+
+    #[test]
+    fn containing_floors_points() {
+        assert_eq!(BlockPos::containing([-0.5, 0.5, -1.0]), BlockPos::new(-1, 0, -1));
+        assert_eq!(ChunkPos::containing([-0.5, 31.9, 32.0]), ChunkPos::new(-1, 0, 1));
+        assert_eq!(ChunkPos::containing([-32.0, -32.5, 0.0]), ChunkPos::new(-1, -2, 0));
+    }
+
+    #[test]
+    fn neighbors_cross_chunk_borders() {
+        let pos = BlockPos::new(-1, 0, 31);
+        let neighbors = pos.neighbors();
+        assert_eq!(neighbors[0], BlockPos::new(0, 0, 31));
+        assert_eq!(neighbors[0].chunk(), ChunkPos::new(0, 0, 0));
+        assert_eq!(neighbors[2].chunk(), ChunkPos::new(-1, -1, 0));
+        assert_eq!(neighbors[4].chunk(), ChunkPos::new(-1, 0, 1));
+        for (neighbor, offset) in neighbors.iter().zip(NEIGHBOR_OFFSETS) {
+            assert_eq!(*neighbor - pos, BlockPos::from(offset));
+        }
+    }
+
+    #[test]
+    fn local_offsets_stay_in_chunk() {
+        let corner = LocalPos::new(0, 31, 0).unwrap();
+        assert_eq!(corner.offset([-1, 0, 0]), None);
+        assert_eq!(corner.offset([0, 1, 0]), None);
+        assert_eq!(corner.offset([1, -1, 0]), LocalPos::new(1, 30, 0));
+        assert_eq!(LocalPos::new(32, 0, 0), None);
+        assert_eq!(corner.on_border(), [false, true, false, true, false, true]);
+        assert_eq!(LocalPos::new(31, 0, 31).unwrap().on_border(), [true, false, true, false, true, false]);
+        assert_eq!(LocalPos::new(1, 30, 5).unwrap().on_border(), [false; 6]);
+        assert_eq!(LocalPos::all().count(), CHUNK_SIZE.0 * CHUNK_SIZE.1 * CHUNK_SIZE.2);
+    }
+
+    #[test]
+    fn chunk_distance() {
+        let a = ChunkPos::new(-2, 5, 0);
+        assert_eq!(a.axis_distance(ChunkPos::new(1, 4, 0)), 3);
+        assert_eq!(ChunkPos::new(i32::MIN, 0, 0).axis_distance(ChunkPos::new(i32::MAX, 0, 0)), u32::MAX);
+        assert_eq!(a + ChunkPos::new(1, 1, 1) - a, ChunkPos::new(1, 1, 1));
+    }
+}
@@ -0,0 +1,129 @@
+use crate::pos::BlockPos;
+
+/// Block hit by a ray
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayHit {
+    pub pos: BlockPos,
+    /// Normal of the face the ray entered the block through, zero if the ray started inside of the block
+    pub normal: [i32; 3],
+    /// Point the ray entered the block at
+    pub point: [f32; 3],
+    /// Distance from the start of the ray to `point`
+    pub distance: f32,
+}
+
+impl RayHit {
+    /// Returns the block in front of the face that was hit, where a block placed against it goes.
+    /// Returns none if the ray started inside of the block, so there is no face
+    pub fn adjacent(&self) -> Option<BlockPos> {
+        if self.normal == [0; 3] {
+            return None;
+        }
+        let [dx, dy, dz] = self.normal;
+        Some(self.pos.offset(dx, dy, dz))
+    }
+}
+
+/// Walks the blocks along a ray in order, starting with the block containing `origin` (DDA). `hits` returns
+/// whether a block is hit, or none to stop the ray there (like at unloaded chunks).
+/// Returns none if no block was hit within `max_distance`
+pub fn raycast(
+    origin: [f64; 3],
+    direction: [f32; 3],
+    max_distance: f32,
+    mut hits: impl FnMut(BlockPos) -> Option<bool>,
+) -> Option<RayHit> {
+    let length = direction.iter().map(|d| (*d as f64).powi(2)).sum::<f64>().sqrt();
+    if length == 0.0 || !length.is_finite() {
+        return None;
+    }
+    let direction = direction.map(|d| d as f64 / length);
+
+    let start = BlockPos::containing(origin);
+    let mut pos = [start.x, start.y, start.z];
+    let mut step = [0; 3];
+    // Distance along the ray to cross one block on each axis
+    let mut t_delta = [f64::INFINITY; 3];
+    // Distance along the ray to the next block boundary on each axis
+    let mut t_max = [f64::INFINITY; 3];
+    for axis in 0..3 {
+        let d = direction[axis];
+        if d == 0.0 {
+            continue;
+        }
+        let block = pos[axis] as f64;
+        step[axis] = d.signum() as i32;
+        t_delta[axis] = 1.0 / d.abs();
+        t_max[axis] = if d > 0.0 {
+            (block + 1.0 - origin[axis]) / d
+        } else {
+            (block - origin[axis]) / d
+        };
+    }
+
+    let mut t = 0.0;
+    let mut normal = [0; 3];
+    while t <= max_distance as f64 {
+        let block = BlockPos::new(pos[0], pos[1], pos[2]);
+        if hits(block)? {
+            return Some(RayHit {
+                pos: block,
+                normal,
+                point: [0, 1, 2].map(|axis| (origin[axis] + direction[axis] * t) as f32),
+                distance: t as f32,
+            });
+        }
+
+        let axis = (0..3).min_by(|a, b| t_max[*a].total_cmp(&t_max[*b])).unwrap();
+        t = t_max[axis];
+        t_max[axis] += t_delta[axis];
+        pos[axis] += step[axis];
+        normal = [0; 3];
+        normal[axis] = -step[axis];
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ray_hits_first_block_face() {
+        let wall = |pos: BlockPos| Some(pos.x >= 3);
+        let hit = raycast([0.5, 0.5, 0.5], [1.0, 0.0, 0.0], 10.0, wall).unwrap();
+        assert_eq!(hit.pos, BlockPos::new(3, 0, 0));
+        assert_eq!(hit.normal, [-1, 0, 0]);
+        assert_eq!(hit.point, [3.0, 0.5, 0.5]);
+        assert_eq!(hit.distance, 2.5);
+        assert_eq!(hit.adjacent(), Some(BlockPos::new(2, 0, 0)));
+
+        // Diagonal rays through negative coordinates
+        let floor = |pos: BlockPos| Some(pos.y < -2);
+        let hit = raycast([-0.5, 0.0, -0.5], [-1.0, -1.0, 0.0], 10.0, floor).unwrap();
+        assert_eq!(hit.normal, [0, 1, 0]);
+        assert_eq!(hit.pos.y, -3);
+        assert!((hit.point[1] + 2.0).abs() < 1e-5);
+        assert!((hit.distance - 2.0 * 2f32.sqrt()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn ray_stops_at_distance_and_unloaded_blocks() {
+        let wall = |pos: BlockPos| Some(pos.z <= -5);
+        assert!(raycast([0.5, 0.5, 0.5], [0.0, 0.0, -1.0], 4.0, wall).is_none());
+        assert!(raycast([0.5, 0.5, 0.5], [0.0, 0.0, -1.0], 6.0, wall).is_some());
+
+        let unloaded = |pos: BlockPos| if pos.z < -2 { None } else { Some(false) };
+        assert!(raycast([0.5, 0.5, 0.5], [0.0, 0.0, -1.0], 10.0, unloaded).is_none());
+        assert!(raycast([0.5, 0.5, 0.5], [0.0; 3], 10.0, wall).is_none());
+    }
+
+    #[test]
+    fn ray_starting_inside_block_hits_it() {
+        let hit = raycast([0.5, 0.5, 0.5], [0.0, 1.0, 0.0], 4.0, |_| Some(true)).unwrap();
+        assert_eq!(hit.pos, BlockPos::new(0, 0, 0));
+        assert_eq!(hit.normal, [0; 3]);
+        assert_eq!(hit.distance, 0.0);
+        assert_eq!(hit.adjacent(), None);
+    }
+}
@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+use std::fs::File;
+
+use serde::{Deserialize, Serialize};
+
+use crate::block_entity::BlockEntityKind;
+use crate::crafting::RecipeBook;
+use crate::inventory::ItemStack;
+
+/// Holds the static game data loaded from `res/`
+pub struct Registry {
+    pub items: ItemRegistry,
+    pub blocks: BlockRegistry,
+    pub recipes: RecipeBook,
+}
+
+impl Registry {
+    /// Loads items, blocks and recipes from the json files in `dir`
+    pub fn load(dir: &str) -> Registry {
+        let items = ItemRegistry::load(&format!("{}/items.json", dir));
+        let blocks = BlockRegistry::load(&format!("{}/blocks.json", dir));
+        let recipes = RecipeBook::load(&format!("{}/recipes.json", dir), &items);
+        Registry {
+            items,
+            blocks,
+            recipes,
+        }
+    }
+}
+
+/// Static information about an item type
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ItemInfo {
+    pub name: String,
+    #[serde(default = "default_max_stack")]
+    pub max_stack: u16,
+    /// Id of the block placed by this item, if any
+    #[serde(default)]
+    pub block: Option<u16>,
+    /// Tool properties if the item is a tool
+    #[serde(default)]
+    pub tool: Option<ToolInfo>,
+    /// Id of the block whose texture is used to draw the item when dropped, for items that aren't blocks
+    #[serde(default)]
+    pub icon: Option<u16>,
+}
+
+fn default_max_stack() -> u16 {
+    64
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolKind {
+    Pickaxe,
+    Shovel,
+    Axe,
+}
+
+/// Static information about a tool item
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ToolInfo {
+    pub kind: ToolKind,
+    pub tier: u8,
+    /// Number of blocks the tool can break before it is used up
+    pub durability: u16,
+    /// Mining speed multiplier per block material when the tool is the correct one for the block
+    #[serde(default)]
+    pub efficiency: HashMap<String, f32>,
+}
+
+/// Static information about a block type
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BlockInfo {
+    pub name: String,
+    /// Size of the crafting grid opened when the block is interacted with, if any
+    #[serde(default)]
+    pub crafting_grid: Option<usize>,
+    /// Seconds needed to break the block by hand
+    #[serde(default = "default_hardness")]
+    pub hardness: f32,
+    #[serde(default)]
+    pub material: String,
+    /// Tool kind that mines this block faster
+    #[serde(default)]
+    pub tool: Option<ToolKind>,
+    /// Minimum tier of the correct tool for the block to be mined faster and drop items
+    #[serde(default)]
+    pub min_tier: u8,
+    /// If true, the block drops nothing unless it is mined with the correct tool
+    #[serde(default)]
+    pub requires_tool: bool,
+    /// Item dropped by the block. Defaults to the item with the same id as the block
+    #[serde(default)]
+    pub drop: Option<u16>,
+    /// Kind of block entity that stores extra state for the block, if any
+    #[serde(default)]
+    pub block_entity: Option<BlockEntityKind>,
+    /// If true, the block is a fluid the player looks through when mining and placing blocks
+    #[serde(default)]
+    pub fluid: bool,
+}
+
+fn default_hardness() -> f32 {
+    1.0
+}
+
+impl BlockInfo {
+    /// Returns true if `tool` is the correct tool for this block
+    pub fn is_correct_tool(&self, tool: Option<&ToolInfo>) -> bool {
+        match (self.tool, tool) {
+            (Some(kind), Some(tool)) => kind == tool.kind && tool.tier >= self.min_tier,
+            _ => false,
+        }
+    }
+
+    /// Returns true if the block drops its item when mined with `tool`
+    pub fn can_harvest(&self, tool: Option<&ToolInfo>) -> bool {
+        !self.requires_tool || self.is_correct_tool(tool)
+    }
+
+    /// Returns how many units of hardness are removed per second when mined with `tool`
+    pub fn mining_speed(&self, tool: Option<&ToolInfo>) -> f32 {
+        if self.is_correct_tool(tool) {
+            tool.and_then(|tool| tool.efficiency.get(&self.material))
+                .copied()
+                .unwrap_or(1.0)
+        } else if self.can_harvest(tool) {
+            1.0
+        } else {
+            WRONG_TOOL_SPEED
+        }
+    }
+}
+
+/// Mining speed multiplier for blocks mined without the tool they require
+const WRONG_TOOL_SPEED: f32 = 0.3;
+
+#[derive(Serialize, Deserialize)]
+struct ItemRegistryInfo {
+    items: HashMap<u16, ItemInfo>,
+}
+
+/// Holds the information of every item type, keyed by item id
+pub struct ItemRegistry {
+    items: HashMap<u16, ItemInfo>,
+}
+
+impl ItemRegistry {
+    /// Loads the item registry from a json file
+    pub fn load(path: &str) -> ItemRegistry {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                panic!("Error opening {}: {}", path, e);
+            }
+        };
+
+        let info: ItemRegistryInfo = match serde_json::from_reader(file) {
+            Ok(v) => v,
+            Err(e) => {
+                panic!("Error parsing {}: {}", path, e);
+            }
+        };
+
+        ItemRegistry::new(info.items)
+    }
+
+    pub fn new(items: HashMap<u16, ItemInfo>) -> ItemRegistry {
+        ItemRegistry { items }
+    }
+
+    /// Returns item info based on id. Returns none if the item doesn't exist
+    pub fn get(&self, id: u16) -> Option<&ItemInfo> {
+        self.items.get(&id)
+    }
+
+    /// Creates a stack of `count` items of type `id`, clamped to the max stack size.
+    /// Returns none if the item doesn't exist
+    pub fn stack(&self, id: u16, count: u16) -> Option<ItemStack> {
+        self.get(id).map(|info| {
+            let stack = ItemStack::new(id, count.min(info.max_stack), info.max_stack);
+            match &info.tool {
+                Some(tool) => stack.with_durability(tool.durability),
+                None => stack,
+            }
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct BlockRegistryInfo {
+    blocks: HashMap<u16, BlockInfo>,
+}
+
+/// Holds the information of every block type, keyed by block id
+pub struct BlockRegistry {
+    blocks: HashMap<u16, BlockInfo>,
+}
+
+impl BlockRegistry {
+    /// Loads the block registry from a json file
+    pub fn load(path: &str) -> BlockRegistry {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                panic!("Error opening {}: {}", path, e);
+            }
+        };
+
+        let info: BlockRegistryInfo = match serde_json::from_reader(file) {
+            Ok(v) => v,
+            Err(e) => {
+                panic!("Error parsing {}: {}", path, e);
+            }
+        };
+
+        BlockRegistry::new(info.blocks)
+    }
+
+    pub fn new(blocks: HashMap<u16, BlockInfo>) -> BlockRegistry {
+        BlockRegistry { blocks }
+    }
+
+    /// Returns block info based on id. Returns none for air and unknown blocks
+    pub fn get(&self, id: u16) -> Option<&BlockInfo> {
+        self.blocks.get(&id)
+    }
+
+    /// Returns the item dropped by block `id` when mined with `tool`, if any
+    pub fn drop(&self, id: u16, tool: Option<&ToolInfo>) -> Option<u16> {
+        let info = self.get(id)?;
+        if info.can_harvest(tool) {
+            Some(info.drop.unwrap_or(id))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(json: &str) -> BlockInfo {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn tool(kind: ToolKind, tier: u8) -> ToolInfo {
+        ToolInfo {
+            kind,
+            tier,
+            durability: 10,
+            efficiency: HashMap::from([("stone".to_string(), 4.0)]),
+        }
+    }
+
+    fn stone() -> BlockInfo {
+        block(r#"{"name": "stone", "material": "stone", "tool": "pickaxe", "min_tier": 1, "requires_tool": true, "drop": 4}"#)
+    }
+
+    fn dirt() -> BlockInfo {
+        block(r#"{"name": "dirt", "material": "dirt", "tool": "shovel"}"#)
+    }
+
+    #[test]
+    fn tools_mine_by_kind_and_tier() {
+        use ToolKind::*;
+        let (stone, dirt) = (stone(), dirt());
+        // Block, tool, correct tool, harvests, mining speed
+        let cases = [
+            (&stone, Some(tool(Pickaxe, 1)), true, true, 4.0),
+            (&stone, Some(tool(Pickaxe, 3)), true, true, 4.0),
+            (&stone, Some(tool(Pickaxe, 0)), false, false, WRONG_TOOL_SPEED),
+            (&stone, Some(tool(Shovel, 3)), false, false, WRONG_TOOL_SPEED),
+            (&stone, None, false, false, WRONG_TOOL_SPEED),
+            (&dirt, Some(tool(Shovel, 0)), true, true, 1.0),
+            (&dirt, Some(tool(Pickaxe, 3)), false, true, 1.0),
+            (&dirt, None, false, true, 1.0),
+        ];
+        for (i, (block, tool, correct, harvests, speed)) in cases.iter().enumerate() {
+            let tool = tool.as_ref();
+            assert_eq!(block.is_correct_tool(tool), *correct, "case {}", i);
+            assert_eq!(block.can_harvest(tool), *harvests, "case {}", i);
+            assert_eq!(block.mining_speed(tool), *speed, "case {}", i);
+        }
+    }
+
+    #[test]
+    fn blocks_drop_items_when_harvested() {
+        let registry = BlockRegistry::new(HashMap::from([(1, stone()), (2, dirt())]));
+        assert_eq!(registry.drop(1, Some(&tool(ToolKind::Pickaxe, 1))), Some(4));
+        assert_eq!(registry.drop(1, Some(&tool(ToolKind::Pickaxe, 0))), None);
+        assert_eq!(registry.drop(1, None), None);
+        assert_eq!(registry.drop(2, None), Some(2));
+        assert_eq!(registry.drop(9, None), None);
+    }
+}
@@ -0,0 +1,765 @@
+use crate::cache::{CacheStats, LruCache};
+use crate::camera::{Camera, Frustum};
+use crate::chunk::*;
+use crate::chunk_mesh::*;
+use crate::daylight::Lighting;
+use crate::item_entity;
+use crate::loader::{
+    chunk_priority, in_distance, in_region, player_chunk, LoadArea, LoadDistance, LoadShape, UNLOAD_MARGIN,
+};
+use crate::player::Player;
+use crate::pos::{BlockPos, ChunkPos};
+use crate::scheduler::JobQueue;
+use crate::selection;
+use crate::worker::{default_threads, WorkerPool, WorkerStats};
+use crate::texture::TextureMap;
+use crate::world::World;
+use glium::Surface;
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// Mesh data built by a worker thread
+enum MeshSections {
+    /// The whole mesh, with the sections of the faces bordering unloaded neighbors
+    Full(MeshData, [Option<MeshData>; 6]),
+    /// Sections of the faces bordering neighbors that were loaded after the mesh was built
+    Borders([Option<MeshData>; 6]),
+}
+
+/// Consists of the chunk coordinate, the versions the mesh was built from and the mesh data
+type BuiltMesh = (ChunkPos, MeshVersions, MeshSections);
+
+/// Snapshots of the 6 surrounding chunks of a chunk, none for the ones that aren't loaded
+type NeighborSnapshots = [Option<ChunkSnapshot>; 6];
+
+/// Part of a chunk mesh to build
+#[derive(Clone, Copy)]
+enum MeshKind {
+    Full,
+    /// Only the faces bordering the given neighbors
+    Borders([bool; 6]),
+}
+
+/// Snapshots of a chunk and its surrounding chunks a worker thread builds a mesh from
+struct MeshJob {
+    /// Epoch the job was queued in
+    epoch: u64,
+    coord: ChunkPos,
+    kind: MeshKind,
+    chunk: ChunkSnapshot,
+    neighbors: NeighborSnapshots,
+    /// Versions of the snapshots
+    versions: MeshVersions,
+    unloaded: UnloadedNeighbor,
+    /// True if the chunk has a mesh that is outdated because of an edit
+    edited: bool,
+}
+
+/// Extra priority given to meshes of chunks outside of the view frustum so they are built last
+const NOT_VISIBLE_PRIORITY: f32 = 1e6;
+/// Priority given to remeshing edited chunks so edits show up before any other mesh is built
+const EDITED_PRIORITY: f32 = -1e7;
+
+/// Default number of bytes of GPU memory used by meshes of chunks out of render distance
+pub const DEFAULT_MESH_CACHE_BUDGET: usize = 64 * 1024 * 1024;
+
+/// Color of the outline drawn around the block the player is looking at
+const OUTLINE_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+/// Cosine of the angle the view has to turn by before the queued meshes are reprioritized for the new frustum
+const REPRIORITIZE_TURN_COS: f32 = 0.985;
+/// Fraction of the render distance that fog starts at
+const FOG_START: f32 = 0.6;
+
+/// Responsible for generating meshes of the chunks in a world within render distance, uploading them to the GPU and rendering them.
+/// Meshes are generated without a window, only uploading and rendering need a display
+pub struct ChunkRenderer {
+    /// Threads building meshes. Declared first so they are stopped before the channel is closed
+    workers: WorkerPool<MeshJob>,
+    mesh_map: HashMap<ChunkPos, ChunkMesh>,
+    /// Meshes that left the render distance, kept so they don't have to be rebuilt if the player comes back
+    mesh_cache: LruCache<ChunkPos, ChunkMesh>,
+    queued_meshes: HashSet<ChunkPos>,
+    render_distance: LoadDistance,
+    /// How faces bordering unloaded chunks are meshed until the chunks are loaded
+    unloaded_neighbors: UnloadedNeighbor,
+    /// Maximum anisotropic filtering of the block textures
+    anisotropy: u16,
+    /// Built meshes, none for the jobs the workers skipped
+    mesh_rx: std::sync::mpsc::Receiver<(ChunkPos, Option<(MeshVersions, MeshSections)>)>,
+    mesh_q: JobQueue<MeshJob>,
+    /// Area queued jobs are still needed for, shared with the worker threads
+    load_area: Arc<RwLock<LoadArea>>,
+    cancelled: Arc<AtomicUsize>,
+    /// Number of meshes discarded because their chunk changed while they were built
+    outdated: usize,
+    /// Chunk and view direction of the player the queued meshes were last prioritized for
+    prioritized_view: Option<(ChunkPos, [f32; 3])>,
+
+    /// Mesh data received from the workers that still has to be uploaded
+    needs_build: Vec<BuiltMesh>,
+    /// Chunks with mesh data in `needs_build`, to look them up without going through the list
+    built: HashSet<ChunkPos>,
+    /// Chunks to queue meshes for, with the part of the mesh to build and whether it is because of an edit
+    to_generate: Vec<(ChunkPos, MeshKind, bool)>,
+
+    texture_info: Arc<HashMap<u16, [[[f32; 2]; 4]; 6]>>,
+}
+
+impl ChunkRenderer {
+    /// Creates a new chunk renderer using the texture coordinates of every block in `texture_info`
+    pub fn new(texture_info: Arc<HashMap<u16, [[[f32; 2]; 4]; 6]>>) -> Self {
+        // Distance from camera that chunks are rendered (number of chunks). Smaller than the load distance,
+        // so the chunks at its edge are meshed with their neighbors loaded
+        let render_distance = LoadDistance::new(LoadShape::Cylinder, 18, 8);
+
+        // Queue of chunk data that worker threads build meshes for, nearest visible chunks first
+        let mesh_q: JobQueue<MeshJob> = JobQueue::new(render_distance.max_chunks());
+
+        let load_area = Arc::new(RwLock::new(LoadArea::new(render_distance)));
+        let cancelled = Arc::new(AtomicUsize::new(0));
+
+        // Channel for sending meshes back to main thread
+        let (mesh_tx, mesh_rx) = std::sync::mpsc::channel();
+
+        // Threads for building meshes
+        let workers = {
+            let tx = mesh_tx.clone();
+            let texture_info = texture_info.clone();
+            let load_area = load_area.clone();
+            let cancelled = cancelled.clone();
+
+            let work = move |job: &MeshJob| {
+                let MeshJob { epoch, coord, kind, ref chunk, ref neighbors, versions, unloaded, .. } = *job;
+
+                // Skip meshes of chunks the player moved away from after they were queued
+                if !load_area.read().unwrap().needs(epoch, &coord) {
+                    cancelled.fetch_add(1, Ordering::Relaxed);
+                    match tx.send((coord, None)) {
+                        Ok(_) => (),
+                        Err(e) => println!("Error sending mesh data to main thread: {}", e),
+                    }
+                    return;
+                }
+
+                // Generate mesh data from the snapshots, so edits on the main thread never wait for it
+                let mesh_data = match kind {
+                    MeshKind::Full => {
+                        let (main, borders) = chunk.gen_mesh(neighbors, unloaded, &texture_info);
+                        MeshSections::Full(main, borders)
+                    }
+                    MeshKind::Borders(faces) => {
+                        let mut borders: [Option<MeshData>; 6] = Default::default();
+                        for (face, border) in borders.iter_mut().enumerate().filter(|(face, _)| faces[*face]) {
+                            *border = Some(chunk.gen_border_mesh(face, neighbors[face].as_ref(), unloaded, &texture_info));
+                        }
+                        MeshSections::Borders(borders)
+                    }
+                };
+
+                // Send mesh data to main thread
+                match tx.send((coord, Some((versions, mesh_data)))) {
+                    Ok(_) => (),
+                    Err(e) => {
+                        println!("Error sending mesh data to main thread: {}", e);
+                    }
+                }
+            };
+            // Meshes that failed to build are queued again by a later update
+            let on_panic = move |job: MeshJob| match mesh_tx.send((job.coord, None)) {
+                Ok(_) => (),
+                Err(e) => println!("Error sending mesh data to main thread: {}", e),
+            };
+            WorkerPool::new("Mesh", default_threads(), mesh_q.clone(), work, on_panic)
+        };
+
+        ChunkRenderer {
+            workers,
+            mesh_map: HashMap::new(),
+            mesh_cache: LruCache::new(DEFAULT_MESH_CACHE_BUDGET),
+            queued_meshes: HashSet::new(),
+            render_distance,
+            unloaded_neighbors: UnloadedNeighbor::Air,
+            anisotropy: 32,
+            mesh_rx,
+            mesh_q,
+            load_area,
+            cancelled,
+            outdated: 0,
+            prioritized_view: None,
+            needs_build: Vec::new(),
+            built: HashSet::new(),
+            to_generate: Vec::with_capacity(render_distance.max_chunks()),
+            texture_info,
+        }
+    }
+
+    /// Queues mesh generation for loaded chunks within render distance that have no mesh or changed,
+    /// receives generated mesh data and unloads meshes out of render distance.
+    /// To be called on the main thread once per game tick, after the world is updated
+    pub fn update(&mut self, world: &World, player: &Player) {
+        let player_chunk = player_chunk(player);
+        let frustum = player.get_camera().frustum();
+
+        // When the player enters another chunk, start a new epoch and drop the queued jobs that are no longer needed
+        if self.load_area.write().unwrap().set_center(&player_chunk) {
+            self.drop_unneeded_jobs();
+        }
+        let epoch = self.load_area.read().unwrap().epoch;
+
+        // Reorder the queued meshes when the player moved to another chunk or turned far enough to see other chunks
+        let direction = player.get_camera().direction();
+        let changed = match self.prioritized_view {
+            None => true,
+            Some((chunk, previous)) => {
+                let cos: f32 = previous.iter().zip(direction).map(|(a, b)| a * b).sum();
+                chunk != player_chunk || cos < REPRIORITIZE_TURN_COS
+            }
+        };
+        if changed {
+            self.mesh_q
+                .reprioritize(|job| mesh_priority(&player_chunk, &frustum, &job.coord, job.edited));
+            self.prioritized_view = Some((player_chunk, direction));
+        }
+
+        // Check loaded chunks if they are in render distance and if their meshes are loaded.
+        // If not, add them to list of meshes to be generated
+        for (coord, chunk) in world.loader().chunks() {
+            if !chunk.read().unwrap().is_empty()
+                && in_distance(player, coord, self.render_distance)
+            {
+                match self.mesh_map.get(coord) {
+                    None => {
+                        // Without a display (tests, servers) the mesh data waits for an upload that never comes
+                        if self.queued_meshes.contains(coord) || self.has_mesh(coord) {
+                            continue;
+                        }
+                        // Reuse the cached mesh if neither the chunk nor its neighbors changed since it was built
+                        let (snapshot, neighbors) = snapshots(world, &chunk.read().unwrap());
+                        let versions = mesh_versions(&snapshot, &neighbors);
+                        if let Some(mesh) = self.mesh_cache.take_if(coord, |mesh| is_current(mesh.versions(), &versions)) {
+                            chunk.write().unwrap().set_updated();
+                            self.mesh_map.insert(*coord, mesh);
+                            continue;
+                        }
+                        chunk.write().unwrap().request_update();
+                        self.to_generate.push((*coord, MeshKind::Full, false));
+                    }
+                    Some(mesh) => {
+                        let needs_update = chunk.read().unwrap().needs_update();
+                        if !needs_update && self.queued_meshes.contains(coord) {
+                            continue;
+                        }
+                        let mut loaded = mesh.unloaded_neighbors();
+                        for (loaded, neighbor) in loaded.iter_mut().zip(coord.neighbors()) {
+                            *loaded &= world.loader().chunk(&neighbor).is_some();
+                        }
+                        if let Some((kind, edited)) = remesh(needs_update, loaded) {
+                            self.to_generate.push((*coord, kind, edited));
+                        }
+                    }
+                }
+            }
+        }
+
+        // Find neighbor chunks and send chunk data and neighbors' chunk data to worker thread for mesh building
+        for (coord, kind, edited) in &self.to_generate {
+            if self.queued_meshes.contains(coord) {
+                continue;
+            }
+
+            // If the queue is full, the least urgent mesh is left out and queued again by a later update
+            let priority = mesh_priority(&player_chunk, &frustum, coord, *edited);
+            self.queued_meshes.insert(*coord);
+            let (chunk, neighbors) = snapshots(world, &world.loader().chunk(coord).unwrap().read().unwrap());
+            let job = MeshJob {
+                epoch,
+                coord: *coord,
+                kind: *kind,
+                versions: mesh_versions(&chunk, &neighbors),
+                chunk,
+                neighbors,
+                unloaded: self.unloaded_neighbors,
+                edited: *edited,
+            };
+            if let Some(rejected) = self.mesh_q.push(job, priority) {
+                self.queued_meshes.remove(&rejected.coord);
+            }
+        }
+
+        // Receive mesh data from worker threads
+        while let Ok((coord, built)) = self.mesh_rx.try_recv() {
+            self.queued_meshes.remove(&coord);
+            // Skipped jobs are queued again by a later update if the chunk is still in render distance
+            let (versions, mesh_data) = match built {
+                Some(built) => built,
+                None => continue,
+            };
+            if let Some(chunk) = world.loader().chunk(&coord) {
+                // Meshes of chunks edited while they were built are discarded. The chunks stay marked for an update,
+                // so every edit made in the meantime is included in a single remesh.
+                // Neighbors loaded in the meantime only need a border remesh
+                let (snapshot, neighbors) = snapshots(world, &chunk.read().unwrap());
+                if !is_current(&versions, &mesh_versions(&snapshot, &neighbors)) {
+                    self.outdated += 1;
+                    continue;
+                }
+                if let MeshSections::Full(..) = mesh_data {
+                    chunk.write().unwrap().set_updated();
+                }
+                self.needs_build.push((coord, versions, mesh_data));
+                self.built.insert(coord);
+            }
+        }
+
+        // Move meshes out of render distance into the cache
+        let keep_distance = self.render_distance.loosened(UNLOAD_MARGIN);
+        let unloaded: Vec<ChunkPos> = self
+            .mesh_map
+            .keys()
+            .filter(|coord| !in_distance(player, coord, keep_distance))
+            .copied()
+            .collect();
+        for coord in unloaded {
+            let mesh = self.mesh_map.remove(&coord).unwrap();
+            let size = mesh.size();
+            self.mesh_cache.insert(coord, mesh, size);
+        }
+        self.needs_build
+            .retain(|(coord, _, _)| in_distance(player, coord, keep_distance));
+        self.built.retain(|coord| in_distance(player, coord, keep_distance));
+
+        // Clear temporary lists
+        self.to_generate.clear();
+    }
+
+    /// Drops the queued jobs of chunks out of the load area, after it was moved or resized
+    fn drop_unneeded_jobs(&mut self) {
+        let area = self.load_area.read().unwrap();
+        for job in self.mesh_q.retain(|job| area.needs(job.epoch, &job.coord)) {
+            self.queued_meshes.remove(&job.coord);
+            self.cancelled.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Builds meshes from received mesh data and inserts them into mesh map
+    pub fn upload(&mut self, display: &glium::Display) {
+        self.built.clear();
+        for (coord, versions, sections) in self.needs_build.drain(..) {
+            match sections {
+                MeshSections::Full(main, borders) => {
+                    let (vb, ib) = match create_buffers(display, &main) {
+                        Some(buffers) => buffers,
+                        None => continue,
+                    };
+                    let mut mesh = ChunkMesh::new(vb, ib, versions);
+                    for (face, border) in borders.iter().enumerate() {
+                        if let Some(border) = border.as_ref().filter(|border| !border.1.is_empty()) {
+                            mesh.set_border(face, create_buffers(display, border), versions[face + 1]);
+                        }
+                    }
+
+                    self.mesh_cache.remove(&coord);
+                    self.mesh_map.insert(coord, mesh);
+                }
+                MeshSections::Borders(borders) => {
+                    // The mesh is rebuilt entirely if it was unloaded in the meantime
+                    let mesh = match self.mesh_map.get_mut(&coord) {
+                        Some(mesh) => mesh,
+                        None => continue,
+                    };
+                    for (face, border) in borders.iter().enumerate() {
+                        if let Some(border) = border {
+                            let buffers = if border.1.is_empty() {
+                                None
+                            } else {
+                                create_buffers(display, border)
+                            };
+                            mesh.set_border(face, buffers, versions[face + 1]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Renders chunk meshes relative to the camera's chunk. `calculate_projection` must have been called on the camera
+    pub fn render(
+        &self,
+        target: &mut glium::Frame,
+        program: &glium::Program,
+        texture_map: &TextureMap,
+        camera: &Camera,
+        lighting: &Lighting,
+        params: &glium::DrawParameters,
+    ) {
+        let fog_range = self.fog_range();
+        let view_projection = camera.view_projection();
+        let frustum = camera.frustum();
+        let camera_chunk = camera.chunk();
+        for (chunk_coord, chunk_mesh) in &self.mesh_map {
+            if !frustum.contains(chunk_coord) {
+                continue;
+            }
+            let offset = (*chunk_coord - camera_chunk).origin();
+            for (vertices, indices) in chunk_mesh.sections() {
+                match target.draw(
+                    vertices,
+                    indices,
+                    program,
+                    &uniform! {
+                        view_projection: view_projection,
+                        u_light: lighting.light,
+                        light_strength: lighting.light_strength,
+                        ambient: lighting.ambient,
+                        fog_color: lighting.fog_color,
+                        fog_range: fog_range,
+                        diffuse_tex: texture_map.base.sampled().magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest).minify_filter(glium::uniforms::MinifySamplerFilter::LinearMipmapLinear).anisotropy(self.anisotropy),
+                        normal_tex: &texture_map.normal,
+                        chunk_offset: [offset.x as f32, offset.y as f32, offset.z as f32]
+                    },
+                    params,
+                ) {
+                    Ok(_) => (),
+                    Err(e) => {
+                        println!("Error while drawing: {}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Renders the dropped items in chunks that have a mesh, relative to the camera's chunk
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_items(
+        &self,
+        world: &World,
+        display: &glium::Display,
+        target: &mut glium::Frame,
+        program: &glium::Program,
+        texture_map: &TextureMap,
+        camera: &Camera,
+        lighting: &Lighting,
+        params: &glium::DrawParameters,
+    ) {
+        let chunks: Vec<_> = self
+            .mesh_map
+            .keys()
+            .filter_map(|coord| world.loader().chunk(coord))
+            .map(|chunk| chunk.read().unwrap())
+            .filter(|chunk| chunk.has_items())
+            .collect();
+        let (vertices, indices) = item_entity::gen_item_mesh(
+            chunks.iter().flat_map(|chunk| chunk.items()),
+            camera.chunk().origin(),
+            &world.registry().items,
+            &self.texture_info,
+        );
+        if indices.is_empty() {
+            return;
+        }
+
+        let vertex_buffer = match glium::VertexBuffer::new(display, &vertices) {
+            Ok(vb) => vb,
+            Err(e) => {
+                println!("Error creating vertex buffer: {:?}", e);
+                return;
+            }
+        };
+        let index_buffer = match glium::IndexBuffer::new(
+            display,
+            glium::index::PrimitiveType::TrianglesList,
+            &indices,
+        ) {
+            Ok(buf) => buf,
+            Err(e) => {
+                println!("Error creating index buffer: {:?}", e);
+                return;
+            }
+        };
+
+        match target.draw(
+            &vertex_buffer,
+            &index_buffer,
+            program,
+            &uniform! {
+                view_projection: camera.view_projection(),
+                u_light: lighting.light,
+                light_strength: lighting.light_strength,
+                ambient: lighting.ambient,
+                fog_color: lighting.fog_color,
+                fog_range: self.fog_range(),
+                diffuse_tex: texture_map.base.sampled().magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest).minify_filter(glium::uniforms::MinifySamplerFilter::LinearMipmapLinear),
+            },
+            params,
+        ) {
+            Ok(_) => (),
+            Err(e) => {
+                println!("Error while drawing: {}", e);
+            }
+        }
+    }
+
+    /// Draws the outline of the block at `pos` and, if `crack_stage` is some, cracks over its faces.
+    /// Drawn after the chunks, like `render`, relative to the camera's chunk
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_selection(
+        &self,
+        display: &glium::Display,
+        target: &mut glium::Frame,
+        outline_program: &glium::Program,
+        crack_program: &glium::Program,
+        texture_map: &TextureMap,
+        camera: &Camera,
+        pos: BlockPos,
+        crack_stage: Option<u32>,
+        params: &glium::DrawParameters,
+    ) {
+        let origin = camera.chunk().origin();
+        let view_projection = camera.view_projection();
+        // Drawn just outside of the block's faces, so only test the depth against the chunks
+        let params = glium::DrawParameters {
+            depth: glium::Depth {
+                test: glium::draw_parameters::DepthTest::IfLessOrEqual,
+                write: false,
+                ..Default::default()
+            },
+            blend: glium::Blend::alpha_blending(),
+            line_width: Some(2.0),
+            ..params.clone()
+        };
+
+        if let Some(stage) = crack_stage {
+            let tex_coords = texture_map.tex_coords(selection::crack_loc(stage));
+            let mesh = selection::gen_overlay(pos, origin, tex_coords);
+            let (vertices, indices) = match create_buffers(display, &mesh) {
+                Some(buffers) => buffers,
+                None => return,
+            };
+            match target.draw(
+                &vertices,
+                &indices,
+                crack_program,
+                &uniform! {
+                    view_projection: view_projection,
+                    diffuse_tex: texture_map.base.sampled().magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest).minify_filter(glium::uniforms::MinifySamplerFilter::Nearest),
+                },
+                &params,
+            ) {
+                Ok(_) => (),
+                Err(e) => println!("Error while drawing: {}", e),
+            }
+        }
+
+        let vertices = match glium::VertexBuffer::new(display, &selection::gen_outline(pos, origin)) {
+            Ok(vb) => vb,
+            Err(e) => {
+                println!("Error creating vertex buffer: {:?}", e);
+                return;
+            }
+        };
+        match target.draw(
+            &vertices,
+            glium::index::NoIndices(glium::index::PrimitiveType::LinesList),
+            outline_program,
+            &uniform! {
+                view_projection: view_projection,
+                u_color: OUTLINE_COLOR,
+            },
+            &params,
+        ) {
+            Ok(_) => (),
+            Err(e) => println!("Error while drawing: {}", e),
+        }
+    }
+
+    /// Removes the queued mesh jobs of every chunk between `min` and `max` (inclusive, chunk space).
+    /// Chunks that still need a mesh are queued again by the next update. Returns the number of removed jobs
+    pub fn cancel_region(&mut self, min: &ChunkPos, max: &ChunkPos) -> usize {
+        let removed = self.mesh_q.retain(|job| !in_region(&job.coord, min, max));
+        self.cancelled.fetch_add(removed.len(), Ordering::Relaxed);
+        let count = removed.len();
+        for job in removed {
+            self.queued_meshes.remove(&job.coord);
+        }
+        count
+    }
+
+    /// Sets the number of bytes of GPU memory used by meshes of chunks out of render distance
+    pub fn set_mesh_cache_budget(&mut self, bytes: usize) {
+        self.mesh_cache.set_budget(bytes);
+    }
+
+    /// Returns the hits, misses and size of the cache of meshes out of render distance
+    pub fn mesh_cache_stats(&self) -> CacheStats {
+        self.mesh_cache.stats()
+    }
+
+    /// Returns the area around the player that chunks are meshed and rendered in
+    pub fn render_distance(&self) -> LoadDistance {
+        self.render_distance
+    }
+
+    /// Returns the distances from the camera that fog starts at and hides blocks completely at, so chunks fade out
+    /// before the edge of the render distance
+    fn fog_range(&self) -> [f32; 2] {
+        let end = self.render_distance.horizontal as f32 * CHUNK_SIZE.0 as f32;
+        [end * FOG_START, end]
+    }
+
+    /// Sets the area around the player that chunks are meshed and rendered in. Takes effect on the next update
+    pub fn set_render_distance(&mut self, distance: LoadDistance) {
+        self.render_distance = distance;
+        self.load_area.write().unwrap().set_distance(distance);
+        self.drop_unneeded_jobs();
+    }
+
+    /// Sets how faces bordering chunks that aren't loaded yet are meshed. Applies to meshes built afterwards
+    pub fn set_unloaded_neighbors(&mut self, policy: UnloadedNeighbor) {
+        self.unloaded_neighbors = policy;
+    }
+
+    /// Sets the maximum anisotropic filtering of the block textures
+    pub fn set_anisotropy(&mut self, anisotropy: u16) {
+        self.anisotropy = anisotropy;
+    }
+
+    /// Sets the number of threads building meshes
+    pub fn set_worker_threads(&mut self, threads: usize) {
+        self.workers.set_threads(threads);
+    }
+
+    /// Returns the number of jobs, panics and utilisation of every thread building meshes
+    pub fn worker_stats(&self) -> Vec<WorkerStats> {
+        self.workers.stats()
+    }
+
+    /// Returns the number of mesh jobs that were cancelled before they were finished
+    pub fn get_number_of_cancelled_meshes(&self) -> usize {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of meshes that were discarded because their chunk changed while they were built
+    pub fn get_number_of_outdated_meshes(&self) -> usize {
+        self.outdated
+    }
+
+    /// Returns the number of meshes waiting to be uploaded
+    pub fn get_number_of_pending_meshes(&self) -> usize {
+        self.needs_build.len()
+    }
+
+    /// Returns true if a chunk has a mesh or mesh data waiting to be uploaded
+    pub fn has_mesh(&self, coord: &ChunkPos) -> bool {
+        self.mesh_map.contains_key(coord) || self.built.contains(coord)
+    }
+
+    /// Returns the number of loaded meshes
+    pub fn get_number_of_loaded_meshes(&self) -> usize {
+        self.mesh_map.len()
+    }
+}
+
+/// Takes snapshots of a chunk and of its loaded neighbors
+fn snapshots(world: &World, chunk: &Chunk) -> (ChunkSnapshot, NeighborSnapshots) {
+    let neighbors = world
+        .loader()
+        .neighbors(&chunk.coord())
+        .map(|neighbor| neighbor.map(|neighbor| neighbor.read().unwrap().snapshot()));
+    (chunk.snapshot(), neighbors)
+}
+
+/// Returns the versions of a chunk and of the faces of its neighbors touching it, which tell if a mesh built from them is outdated
+fn mesh_versions(chunk: &ChunkSnapshot, neighbors: &NeighborSnapshots) -> MeshVersions {
+    let mut versions = [chunk.version(); 7];
+    for (i, (version, neighbor)) in versions[1..].iter_mut().zip(neighbors).enumerate() {
+        // Faces are ordered in pairs of opposite faces
+        *version = match neighbor {
+            Some(neighbor) => neighbor.border_version(i ^ 1),
+            None => UNLOADED_VERSION,
+        };
+    }
+    versions
+}
+
+/// Returns true if a mesh built from `built` is still usable with `current` versions. Faces built while their neighbor
+/// wasn't loaded count as current, since they are rebuilt on their own once it is
+fn is_current(built: &MeshVersions, current: &MeshVersions) -> bool {
+    built
+        .iter()
+        .zip(current)
+        .enumerate()
+        .all(|(i, (built, current))| built == current || (i > 0 && *built == UNLOADED_VERSION))
+}
+
+/// Creates the GPU buffers of a mesh section
+fn create_buffers<V: glium::Vertex>(
+    display: &glium::Display,
+    (vertices, indices): &(Vec<V>, Vec<u16>),
+) -> Option<(glium::VertexBuffer<V>, glium::IndexBuffer<u16>)> {
+    let vb = match glium::VertexBuffer::new(display, vertices) {
+        Ok(vb) => vb,
+        Err(e) => {
+            println!("Error creating vertex buffer: {:?}", e);
+            return None;
+        }
+    };
+    match glium::IndexBuffer::new(display, glium::index::PrimitiveType::TrianglesList, indices) {
+        Ok(ib) => Some((vb, ib)),
+        Err(e) => {
+            println!("Error creating index buffer: {:?}", e);
+            None
+        }
+    }
+}
+
+/// Returns the part of a chunk's existing mesh to rebuild and whether it is because of an edit, or none if the
+/// mesh is current. Edits rebuild the whole mesh, neighbors `loaded` since the mesh was built only the faces bordering them
+fn remesh(needs_update: bool, loaded: [bool; 6]) -> Option<(MeshKind, bool)> {
+    if needs_update {
+        Some((MeshKind::Full, true))
+    } else if loaded.contains(&true) {
+        Some((MeshKind::Borders(loaded), false))
+    } else {
+        None
+    }
+}
+
+/// Returns the priority of building a chunk mesh. Visible chunks come before chunks outside of the frustum
+fn mesh_priority(player_chunk: &ChunkPos, frustum: &Frustum, coord: &ChunkPos, edited: bool) -> f32 {
+    let priority = chunk_priority(player_chunk, coord);
+    if edited {
+        priority + EDITED_PRIORITY
+    } else if frustum.contains(coord) {
+        priority
+    } else {
+        priority + NOT_VISIBLE_PRIORITY
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits_go_before_border_remeshes() {
+        let player_chunk = ChunkPos::new(0, 0, 0);
+        let frustum = Player::default().get_camera().frustum();
+        let mut loaded = [false; 6];
+        loaded[3] = true;
+
+        // A neighbor loading next to the player doesn't count as an edit
+        let (kind, edited) = remesh(false, loaded).unwrap();
+        assert!(matches!(kind, MeshKind::Borders(faces) if faces == loaded));
+        assert!(!edited);
+        let border = mesh_priority(&player_chunk, &frustum, &ChunkPos::new(1, 0, 0), edited);
+
+        let (kind, edited) = remesh(true, loaded).unwrap();
+        assert!(matches!(kind, MeshKind::Full));
+        let edit = mesh_priority(&player_chunk, &frustum, &ChunkPos::new(10, 0, 10), edited);
+        assert!(edit < border);
+
+        assert!(remesh(false, [false; 6]).is_none());
+    }
+}
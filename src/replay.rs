@@ -0,0 +1,112 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::actions::{Actions, Bindings};
+use crate::file_util::{read_from_file, save_to_file};
+use crate::input::Input;
+use crate::loader::LoadDistance;
+use crate::player::{Player, PlayerState};
+use crate::registry::Registry;
+use crate::world::World;
+
+/// Input of a game tick
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Tick {
+    /// Seconds since the last tick
+    pub delta: f32,
+    pub input: Input,
+}
+
+/// Position of the player and blocks of the world at the end of a recording or replay
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Outcome {
+    pub position: [f64; 3],
+    /// See `World::hash_blocks`
+    pub world_hash: u64,
+}
+
+impl Outcome {
+    pub fn of(player: &Player, world: &World) -> Outcome {
+        Outcome {
+            position: [player.x, player.y, player.z],
+            world_hash: world.hash_blocks(),
+        }
+    }
+}
+
+/// Input of every tick of a game, with the world and player it started with, so it can be played back the same way.
+/// The world has to load its chunks with `World::set_blocking_loads` while recording
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Recording {
+    pub seed: u32,
+    pub load_distance: LoadDistance,
+    pub bindings: Bindings,
+    pub player: PlayerState,
+    pub ticks: Vec<Tick>,
+    /// Outcome at the end of the recording, none until it is finished
+    pub outcome: Option<Outcome>,
+}
+
+impl Recording {
+    /// Starts a recording of the player in a world with `seed` that loads chunks in `load_distance`
+    pub fn new(seed: u32, load_distance: LoadDistance, bindings: Bindings, player: &Player) -> Recording {
+        Recording {
+            seed,
+            load_distance,
+            bindings,
+            player: player.state(),
+            ticks: Vec::new(),
+            outcome: None,
+        }
+    }
+
+    /// Adds the input of a tick, to be called before the tick runs
+    pub fn record(&mut self, delta: f32, input: &Input) {
+        self.ticks.push(Tick {
+            delta,
+            input: input.clone(),
+        });
+    }
+
+    /// Saves the outcome of the recording, to be called after the last tick
+    pub fn finish(&mut self, player: &Player, world: &World) {
+        self.outcome = Some(Outcome::of(player, world));
+    }
+
+    pub fn save(&self, path: &str) {
+        match bincode::serialize(self) {
+            Ok(bytes) => {
+                save_to_file(bytes.as_slice(), path);
+            }
+            Err(e) => println!("Error serializing recording: {}", e),
+        }
+    }
+
+    /// Loads a recording from file. Returns none if the file doesn't exist or is invalid
+    pub fn load(path: &str) -> Option<Recording> {
+        let data = read_from_file(path)?;
+        match bincode::deserialize(data.as_slice()) {
+            Ok(recording) => Some(recording),
+            Err(e) => {
+                println!("Error reading recording: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Plays the recorded input back in a new world saving its chunks to `save_dir`, and returns the outcome.
+    /// `save_dir` has to be empty, or chunks saved by other games are loaded
+    pub fn replay(&self, registry: Arc<Registry>, save_dir: &str) -> Outcome {
+        let mut world = World::new(self.seed, registry, save_dir);
+        world.set_load_distance(self.load_distance);
+        world.set_blocking_loads(true);
+        let mut player = Player::from_state(self.player.clone());
+        let mut actions = Actions::new(self.bindings.clone());
+
+        for tick in &self.ticks {
+            world.tick(&mut player, &mut actions, &tick.input, tick.delta);
+        }
+        Outcome::of(&player, &world)
+    }
+}
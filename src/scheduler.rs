@@ -0,0 +1,222 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::{Arc, Condvar, Mutex};
+
+/// Bounded multi-producer multi-consumer queue that hands out the job with the lowest priority value first.
+/// Jobs with equal priority are handed out in the order they were pushed
+pub struct JobQueue<T> {
+    shared: Arc<Shared<T>>,
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    available: Condvar,
+    capacity: usize,
+}
+
+struct State<T> {
+    jobs: BinaryHeap<Job<T>>,
+    next_seq: u64,
+}
+
+struct Job<T> {
+    priority: f32,
+    seq: u64,
+    item: T,
+}
+
+impl<T> JobQueue<T> {
+    pub fn new(capacity: usize) -> JobQueue<T> {
+        JobQueue {
+            shared: Arc::new(Shared {
+                state: Mutex::new(State {
+                    jobs: BinaryHeap::new(),
+                    next_seq: 0,
+                }),
+                available: Condvar::new(),
+                capacity,
+            }),
+        }
+    }
+
+    /// Queues `item`. If the queue is full, the job with the highest priority value is given back
+    /// instead, which is `item` itself unless a queued job is less urgent
+    pub fn push(&self, item: T, priority: f32) -> Option<T> {
+        let mut state = self.shared.state.lock().unwrap();
+        let seq = state.next_seq;
+        state.next_seq += 1;
+        let job = Job { priority, seq, item };
+
+        if state.jobs.len() < self.shared.capacity {
+            state.jobs.push(job);
+            drop(state);
+            self.shared.available.notify_one();
+            return None;
+        }
+
+        // The queue is full, so it can't be empty and no worker needs to be woken up
+        let mut jobs = std::mem::take(&mut state.jobs).into_vec();
+        let worst = jobs
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.priority.total_cmp(&b.priority))
+            .map(|(i, _)| i);
+        let rejected = match worst {
+            Some(i) if jobs[i].priority > job.priority => {
+                let rejected = jobs.swap_remove(i);
+                jobs.push(job);
+                rejected.item
+            }
+            _ => job.item,
+        };
+        state.jobs = BinaryHeap::from(jobs);
+        Some(rejected)
+    }
+
+    /// Removes the most urgent job, waiting until one is available.
+    /// Returns none once `stop` is set and `wake_all` is called, even if jobs are left
+    pub fn pop(&self, stop: &AtomicBool) -> Option<T> {
+        let mut state = self.shared.state.lock().unwrap();
+        loop {
+            if stop.load(AtomicOrdering::Relaxed) {
+                return None;
+            }
+            if let Some(job) = state.jobs.pop() {
+                return Some(job.item);
+            }
+            state = self.shared.available.wait(state).unwrap();
+        }
+    }
+
+    /// Wakes every thread waiting in `pop`, so they notice their stop flag
+    pub fn wake_all(&self) {
+        // Taking the lock makes sure a thread that checked its flag before it was set is already waiting
+        let _state = self.shared.state.lock().unwrap();
+        self.shared.available.notify_all();
+    }
+
+    /// Removes every queued job for which `keep` returns false, returning the removed jobs
+    pub fn retain(&self, keep: impl Fn(&T) -> bool) -> Vec<T> {
+        let mut state = self.shared.state.lock().unwrap();
+        let (kept, removed): (Vec<_>, Vec<_>) = std::mem::take(&mut state.jobs)
+            .into_vec()
+            .into_iter()
+            .partition(|job| keep(&job.item));
+        state.jobs = BinaryHeap::from(kept);
+        removed.into_iter().map(|job| job.item).collect()
+    }
+
+    /// Recalculates the priority of every queued job
+    pub fn reprioritize(&self, priority: impl Fn(&T) -> f32) {
+        let mut state = self.shared.state.lock().unwrap();
+        let jobs = std::mem::take(&mut state.jobs)
+            .into_vec()
+            .into_iter()
+            .map(|job| Job {
+                priority: priority(&job.item),
+                ..job
+            })
+            .collect();
+        state.jobs = jobs;
+    }
+}
+
+impl<T> Clone for JobQueue<T> {
+    fn clone(&self) -> Self {
+        JobQueue {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> PartialEq for Job<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for Job<T> {}
+
+impl<T> PartialOrd for Job<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Job<T> {
+    /// Reversed so the max-heap pops the lowest priority value, then the oldest job
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .priority
+            .total_cmp(&self.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn pop_all(queue: &JobQueue<u32>) -> Vec<u32> {
+        let stop = AtomicBool::new(false);
+        let mut jobs = Vec::new();
+        while !queue.shared.state.lock().unwrap().jobs.is_empty() {
+            jobs.push(queue.pop(&stop).unwrap());
+        }
+        jobs
+    }
+
+    #[test]
+    fn jobs_pop_lowest_priority_first() {
+        let queue = JobQueue::new(8);
+        for (job, priority) in [(1, 3.0), (2, 1.0), (3, 2.0), (4, 1.0), (5, -1.0)] {
+            assert_eq!(queue.push(job, priority), None);
+        }
+        // Equal priorities keep the order they were pushed in
+        assert_eq!(pop_all(&queue), [5, 2, 4, 3, 1]);
+    }
+
+    #[test]
+    fn full_queue_rejects_least_urgent_job() {
+        let queue = JobQueue::new(2);
+        queue.push(1, 1.0);
+        queue.push(2, 5.0);
+        assert_eq!(queue.push(3, 2.0), Some(2));
+        assert_eq!(queue.push(4, 9.0), Some(4));
+        assert_eq!(queue.push(5, 2.0), Some(5));
+        assert_eq!(pop_all(&queue), [1, 3]);
+    }
+
+    #[test]
+    fn reprioritize_and_retain_jobs() {
+        let queue = JobQueue::new(8);
+        for job in 0..6 {
+            queue.push(job, job as f32);
+        }
+        queue.reprioritize(|job| -(*job as f32));
+        let mut removed = queue.retain(|job| job % 2 == 0);
+        removed.sort();
+        assert_eq!(removed, [1, 3, 5]);
+        assert_eq!(pop_all(&queue), [4, 2, 0]);
+    }
+
+    #[test]
+    fn stopped_pop_returns_none() {
+        let queue: JobQueue<u32> = JobQueue::new(8);
+        let stop = Arc::new(AtomicBool::new(false));
+        let waiting = {
+            let (queue, stop) = (queue.clone(), stop.clone());
+            std::thread::spawn(move || queue.pop(&stop))
+        };
+        std::thread::sleep(Duration::from_millis(20));
+        stop.store(true, AtomicOrdering::Relaxed);
+        queue.wake_all();
+        assert_eq!(waiting.join().unwrap(), None);
+
+        // Even if jobs are left
+        queue.push(1, 0.0);
+        assert_eq!(queue.pop(&stop), None);
+    }
+}
@@ -0,0 +1,119 @@
+use crate::chunk::Faces;
+use crate::pos::BlockPos;
+
+/// Number of crack textures drawn over a block while it is mined, from least to most cracked
+pub const CRACK_STAGES: u32 = 10;
+/// Location in the texture atlas of the first crack texture, the others follow it
+const FIRST_CRACK_LOC: u32 = 0;
+/// Distance the outline and cracks are drawn outside of the block, so its faces don't hide them
+const MARGIN: f32 = 0.003;
+
+#[derive(Copy, Clone, Debug)]
+pub struct LineVertex {
+    position: [f32; 3],
+}
+
+implement_vertex!(LineVertex, position);
+
+#[derive(Copy, Clone, Debug)]
+pub struct OverlayVertex {
+    position: [f32; 3],
+    tex_coords: [f32; 2],
+}
+
+implement_vertex!(OverlayVertex, position, tex_coords);
+
+/// Returns the crack stage of a block mined by `fraction` (0 to 1)
+pub fn crack_stage(fraction: f32) -> u32 {
+    ((fraction.max(0.0) * CRACK_STAGES as f32) as u32).min(CRACK_STAGES - 1)
+}
+
+/// Returns the location in the texture atlas of the crack texture of `stage`
+pub fn crack_loc(stage: u32) -> u32 {
+    FIRST_CRACK_LOC + stage.min(CRACK_STAGES - 1)
+}
+
+/// Builds the 12 edges of the outline of a block as a line list, with positions relative to `origin`
+pub fn gen_outline(pos: BlockPos, origin: BlockPos) -> Vec<LineVertex> {
+    let corner = |[x, y, z]: [i32; 3]| LineVertex {
+        position: [
+            (pos.x - origin.x + x) as f32 + (x as f32 * 2.0 - 1.0) * MARGIN,
+            (pos.y - origin.y + y) as f32 + (y as f32 * 2.0 - 1.0) * MARGIN,
+            (pos.z - origin.z + z) as f32 + (z as f32 * 2.0 - 1.0) * MARGIN,
+        ],
+    };
+
+    let mut vertices = Vec::with_capacity(24);
+    for a in 0..2 {
+        for b in 0..2 {
+            vertices.extend([corner([0, a, b]), corner([1, a, b])]);
+            vertices.extend([corner([a, 0, b]), corner([a, 1, b])]);
+            vertices.extend([corner([a, b, 0]), corner([a, b, 1])]);
+        }
+    }
+    vertices
+}
+
+/// Builds the faces of a block textured with `tex_coords` (from `TextureMap::tex_coords`), with positions relative to `origin`
+pub fn gen_overlay(
+    pos: BlockPos,
+    origin: BlockPos,
+    tex_coords: [[f32; 2]; 4],
+) -> (Vec<OverlayVertex>, Vec<u16>) {
+    const FACE_INDICES: &[u16; 6] = &[2, 1, 0, 0, 3, 2];
+    let mut vertices = Vec::with_capacity(24);
+    let mut indices = Vec::with_capacity(36);
+
+    for face in Faces::ALL {
+        let start = vertices.len() as u16;
+        for (c, (fx, fy, fz)) in face.points.iter().enumerate() {
+            vertices.push(OverlayVertex {
+                position: [
+                    (pos.x - origin.x + fx) as f32 + (*fx as f32 * 2.0 - 1.0) * MARGIN,
+                    (pos.y - origin.y + fy) as f32 + (*fy as f32 * 2.0 - 1.0) * MARGIN,
+                    (pos.z - origin.z + fz) as f32 + (*fz as f32 * 2.0 - 1.0) * MARGIN,
+                ],
+                tex_coords: tex_coords[c],
+            });
+        }
+        indices.extend(FACE_INDICES.iter().map(|i| start + i));
+    }
+    (vertices, indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crack_stages_follow_progress() {
+        assert_eq!(crack_stage(0.0), 0);
+        assert_eq!(crack_stage(0.25), 2);
+        assert_eq!(crack_stage(0.99), 9);
+        assert_eq!(crack_stage(1.5), CRACK_STAGES - 1);
+        assert_eq!(crack_stage(f32::NAN), 0);
+        assert_eq!(crack_loc(20), FIRST_CRACK_LOC + 9);
+    }
+
+    #[test]
+    fn outline_surrounds_block() {
+        let origin = BlockPos::new(-32, 0, 32);
+        let outline = gen_outline(BlockPos::new(-31, 5, 40), origin);
+        assert_eq!(outline.len(), 24);
+        for (axis, min) in [1.0, 5.0, 8.0].into_iter().enumerate() {
+            let values = outline.iter().map(|vertex| vertex.position[axis]);
+            assert_eq!(values.clone().fold(f32::MAX, f32::min), min - MARGIN);
+            assert_eq!(values.fold(f32::MIN, f32::max), min + 1.0 + MARGIN);
+        }
+        // Every edge runs along one axis
+        for edge in outline.chunks(2) {
+            let diff: Vec<f32> = (0..3)
+                .map(|i| (edge[1].position[i] - edge[0].position[i]).abs())
+                .collect();
+            assert_eq!(diff.iter().filter(|d| **d > 0.5).count(), 1);
+        }
+
+        let (vertices, indices) = gen_overlay(BlockPos::new(0, 0, 0), origin, [[0.0; 2]; 4]);
+        assert_eq!((vertices.len(), indices.len()), (24, 36));
+    }
+}
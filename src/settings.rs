@@ -0,0 +1,225 @@
+use serde::{Deserialize, Serialize};
+
+use crate::actions::Bindings;
+use crate::loader::{LoadDistance, LoadShape};
+use crate::worker::default_threads;
+
+/// Sample counts allowed for multisample anti-aliasing, 0 turns it off
+pub const MSAA_SAMPLES: [u16; 5] = [0, 2, 4, 8, 16];
+
+/// Engine settings, loaded from a JSON file at startup. Fields missing from the file keep their default values
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Area around the player that chunks are rendered in. Chunks are loaded one chunk further
+    pub render_distance: LoadDistance,
+    /// Number of threads loading and generating chunks, 0 to pick one based on the number of cores
+    pub chunk_threads: u32,
+    /// Number of threads building meshes, 0 to pick one based on the number of cores
+    pub mesh_threads: u32,
+    /// Seed of the world. Takes effect on restart
+    pub seed: u32,
+    pub window_width: u32,
+    pub window_height: u32,
+    /// Samples per pixel of multisample anti-aliasing, one of `MSAA_SAMPLES`. Takes effect on restart
+    pub msaa: u16,
+    /// Vertical field of view in degrees
+    pub fov: f32,
+    /// Mouse sensitivity
+    pub rot_speed: f32,
+    /// Maximum anisotropic filtering of the block textures
+    pub anisotropy: u16,
+    /// Buttons bound to each action, actions left out keep their default bindings
+    pub bindings: Bindings,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            render_distance: LoadDistance::new(LoadShape::Cylinder, 18, 8),
+            chunk_threads: 0,
+            mesh_threads: 0,
+            seed: 0,
+            window_width: 1920,
+            window_height: 1080,
+            msaa: 16,
+            fov: 69.0,
+            rot_speed: 0.75,
+            anisotropy: 32,
+            bindings: Bindings::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Loads settings from a JSON file, writing the defaults to it if it doesn't exist.
+    /// Invalid values are replaced, and an invalid file is ignored
+    pub fn load(path: &str) -> Settings {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let settings = Settings::default();
+                settings.save(path);
+                return settings;
+            }
+            Err(e) => {
+                println!("Error reading {}: {}", path, e);
+                return Settings::default();
+            }
+        };
+        match serde_json::from_str::<Settings>(&text) {
+            Ok(settings) => settings.validated(),
+            Err(e) => {
+                println!("Error parsing {}, using default settings: {}", path, e);
+                Settings::default()
+            }
+        }
+    }
+
+    /// Saves the settings to a JSON file
+    pub fn save(&self, path: &str) {
+        let json = match serde_json::to_string_pretty(self) {
+            Ok(json) => json,
+            Err(e) => {
+                println!("Error serializing settings: {}", e);
+                return;
+            }
+        };
+        if let Some(parent) = std::path::Path::new(path).parent() {
+            if let Err(e) = std::fs::create_dir_all(parent) {
+                println!("Error creating directory {}: {}", parent.display(), e);
+            }
+        }
+        match std::fs::write(path, json) {
+            Ok(_) => (),
+            Err(e) => println!("Error saving {}: {}", path, e),
+        }
+    }
+
+    /// Returns the settings with every value clamped to the range it is valid in
+    pub fn validated(self) -> Settings {
+        let msaa = MSAA_SAMPLES
+            .iter()
+            .copied()
+            .filter(|samples| *samples <= self.msaa)
+            .max()
+            .unwrap_or(0);
+        let settings = Settings {
+            render_distance: LoadDistance {
+                horizontal: self.render_distance.horizontal.clamp(1, 64),
+                vertical: self.render_distance.vertical.clamp(1, 32),
+                ..self.render_distance
+            },
+            chunk_threads: self.chunk_threads.min(64),
+            mesh_threads: self.mesh_threads.min(64),
+            seed: self.seed,
+            window_width: self.window_width.max(320),
+            window_height: self.window_height.max(240),
+            msaa,
+            fov: clamp_or(self.fov, 30.0, 120.0, Settings::default().fov),
+            rot_speed: clamp_or(self.rot_speed, 0.05, 10.0, Settings::default().rot_speed),
+            anisotropy: self.anisotropy.clamp(1, 32),
+            bindings: self.bindings.clone(),
+        };
+        if settings != self {
+            println!("Invalid settings were replaced: {:?}", settings);
+        }
+        settings
+    }
+
+    /// Returns the area around the player that chunks are loaded in, one chunk past the render distance
+    /// so the chunks at the edge of the render distance are meshed with their neighbors
+    pub fn load_distance(&self) -> LoadDistance {
+        self.render_distance.loosened(1)
+    }
+
+    /// Returns the number of threads loading and generating chunks
+    pub fn chunk_threads(&self) -> usize {
+        match self.chunk_threads {
+            0 => default_threads(),
+            threads => threads as usize,
+        }
+    }
+
+    /// Returns the number of threads building meshes
+    pub fn mesh_threads(&self) -> usize {
+        match self.mesh_threads {
+            0 => default_threads(),
+            threads => threads as usize,
+        }
+    }
+
+    /// Returns the vertical field of view in radians
+    pub fn fov_radians(&self) -> f32 {
+        self.fov.to_radians()
+    }
+}
+
+/// Clamps `value` between `min` and `max`, replacing NaN with `default`
+fn clamp_or(value: f32, min: f32, max: f32, default: f32) -> f32 {
+    if value.is_nan() {
+        default
+    } else {
+        value.clamp(min, max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        let settings: Settings = serde_json::from_str(r#"{ "seed": 7, "fov": 90.0 }"#).unwrap();
+        assert_eq!(
+            settings,
+            Settings {
+                seed: 7,
+                fov: 90.0,
+                ..Settings::default()
+            }
+        );
+        assert_eq!(Settings::default().validated(), Settings::default());
+    }
+
+    #[test]
+    fn invalid_values_are_clamped() {
+        let settings = Settings {
+            render_distance: LoadDistance::new(LoadShape::Sphere, 0, 500),
+            window_width: 10,
+            msaa: 12,
+            fov: f32::NAN,
+            rot_speed: 100.0,
+            anisotropy: 0,
+            ..Settings::default()
+        }
+        .validated();
+        assert_eq!(settings.render_distance, LoadDistance::new(LoadShape::Sphere, 1, 32));
+        assert_eq!(settings.window_width, 320);
+        assert_eq!(settings.msaa, 8);
+        assert_eq!(settings.fov, Settings::default().fov);
+        assert_eq!(settings.rot_speed, 10.0);
+        assert_eq!(settings.anisotropy, 1);
+    }
+
+    #[test]
+    fn settings_are_saved_and_loaded() {
+        let path = std::env::temp_dir().join(format!("vixen-settings-{}.json", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        let _ = std::fs::remove_file(&path);
+
+        // A missing file is created with the defaults
+        assert_eq!(Settings::load(&path), Settings::default());
+        let settings = Settings {
+            mesh_threads: 3,
+            window_height: 720,
+            ..Settings::default()
+        };
+        settings.save(&path);
+        assert_eq!(Settings::load(&path), settings);
+
+        std::fs::write(&path, "not json").unwrap();
+        assert_eq!(Settings::load(&path), Settings::default());
+        let _ = std::fs::remove_file(&path);
+    }
+}
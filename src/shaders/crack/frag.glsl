@@ -0,0 +1,14 @@
+#version 140
+
+in vec2 v_tex_coords;
+
+out vec4 color;
+
+uniform sampler2D diffuse_tex;
+
+void main() {
+    color = texture(diffuse_tex, v_tex_coords);
+    if (color.a < 0.01) {
+        discard;
+    }
+}
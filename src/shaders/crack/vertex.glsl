@@ -0,0 +1,12 @@
+#version 150
+in vec3 position;
+in vec2 tex_coords;
+
+out vec2 v_tex_coords;
+
+uniform mat4 view_projection;
+
+void main() {
+    v_tex_coords = tex_coords;
+    gl_Position = view_projection * vec4(position, 1.0);
+}
@@ -3,19 +3,23 @@
 in vec3 v_normal;
 in vec3 v_position;
 in vec2 v_tex_coords;
+in float v_depth;
 
 out vec4 color;
 
 uniform vec3 u_light;
+uniform float light_strength;
+uniform float ambient;
+uniform vec3 fog_color;
+// Distance from the camera fog starts at and hides blocks completely at
+uniform vec2 fog_range;
 uniform sampler2D diffuse_tex;
 uniform sampler2D normal_tex;
 
-const vec3 skybox_color = vec3(0.2, 0.6, 0.9);
-const float light_strength = 0.95;
-
 void main() {
     vec3 diffuse_color = texture(diffuse_tex, v_tex_coords).rgb;
-    vec3 ambient_color = diffuse_color * 0.15;
+    vec3 ambient_color = diffuse_color * ambient;
     float diffuse = max(light_strength*dot(v_normal, -normalize(u_light)), 0.0);
-    color = vec4(ambient_color + diffuse * diffuse_color, 1.0);
+    float fog = smoothstep(fog_range.x, fog_range.y, v_depth);
+    color = vec4(mix(ambient_color + diffuse * diffuse_color, fog_color, fog), 1.0);
 }
\ No newline at end of file
@@ -5,9 +5,11 @@ in vec2 tex_coords;
 out vec3 v_normal;
 out vec3 v_position;
 out vec2 v_tex_coords;
+out float v_depth;
 
 uniform mat4 view_projection;
-uniform vec3 chunk_coords;
+// Offset of the chunk from the origin of the camera's chunk
+uniform vec3 chunk_offset;
 
 void main() {
     vec3 vertexPos = vec3(float(position & 0x3Fu), float((position & 0xFC0u) >> 6u), float((position & 0x3F000u) >> 12u));
@@ -16,6 +18,7 @@ void main() {
 
     v_tex_coords = tex_coords;//vec2(float(tex_coords & 0xFFFFu) / 1000.0, float((tex_coords & 0xFFFF0000u) >> 16u) / 1000.0);
     v_normal = normal;
-    gl_Position = view_projection * vec4(vertexPos + chunk_coords, 1.0);
+    gl_Position = view_projection * vec4(vertexPos + chunk_offset, 1.0);
     v_position = gl_Position.xyz;// / gl_Position.w;
+    v_depth = gl_Position.w;
 }
\ No newline at end of file
@@ -0,0 +1,22 @@
+#version 140
+
+in vec3 v_normal;
+in vec2 v_tex_coords;
+in float v_depth;
+
+out vec4 color;
+
+uniform vec3 u_light;
+uniform float light_strength;
+uniform float ambient;
+uniform vec3 fog_color;
+uniform vec2 fog_range;
+uniform sampler2D diffuse_tex;
+
+void main() {
+    vec3 diffuse_color = texture(diffuse_tex, v_tex_coords).rgb;
+    vec3 ambient_color = diffuse_color * ambient;
+    float diffuse = max(light_strength*dot(v_normal, -normalize(u_light)), 0.0);
+    float fog = smoothstep(fog_range.x, fog_range.y, v_depth);
+    color = vec4(mix(ambient_color + diffuse * diffuse_color, fog_color, fog), 1.0);
+}
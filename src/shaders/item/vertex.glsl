@@ -0,0 +1,17 @@
+#version 150
+in vec3 position;
+in vec3 normal;
+in vec2 tex_coords;
+
+out vec3 v_normal;
+out vec2 v_tex_coords;
+out float v_depth;
+
+uniform mat4 view_projection;
+
+void main() {
+    v_tex_coords = tex_coords;
+    v_normal = normal;
+    gl_Position = view_projection * vec4(position, 1.0);
+    v_depth = gl_Position.w;
+}
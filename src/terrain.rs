@@ -4,7 +4,7 @@ use noise::NoiseFn;
 use noise::Seedable;
 
 use crate::chunk::*;
-use crate::loader::*;
+use crate::pos::{ChunkPos, LocalPos};
 
 #[derive(Clone)]
 pub struct TerrainGenerator {
@@ -24,9 +24,10 @@ impl TerrainGenerator {
         }
     }
 
-    /// Generate chunk at coord (x,y,z) in chunk space
-    pub fn generate_chunk(&self, (x, y, z): (i32, i32, i32)) -> Chunk {
-        let mut out = Chunk::empty(ChunkCoord { x, y, z });
+    /// Generate chunk at coord in chunk space
+    pub fn generate_chunk(&self, coord: ChunkPos) -> Chunk {
+        let ChunkPos { x, y, z } = coord;
+        let mut out = Chunk::empty(coord);
 
         if y > 4 || y < -4 {
             return out;
@@ -59,7 +60,7 @@ impl TerrainGenerator {
                         } else {
                             3
                         };
-                        out.set_block((i, j, k), Block::new(id, 5.0));
+                        out.set_block(LocalPos::new(i, j, k).unwrap(), Block::new(id));
                     }
                 }
             }
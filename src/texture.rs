@@ -11,6 +11,15 @@ pub struct TextureMap {
     pub base: SrgbTexture2d,
     pub normal: Texture2d,
     pub info: Arc<HashMap<u16, [[[f32;2];4];6]>>,
+    /// Number of textures along each side of the atlas
+    pub grid: u32,
+}
+
+impl TextureMap {
+    /// Returns the texture coordinates of the corners of the texture at `loc` in the atlas, in the order of face points
+    pub fn tex_coords(&self, loc: u32) -> [[f32; 2]; 4] {
+        loc_tex_coords(loc, self.grid)
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -85,25 +94,30 @@ pub fn load_texture_map(base: &str, normal: Option<&str>, display: &glium::Displ
     assert_eq!(image_dimensions.0, image_dimensions.1);
     
     // Process info
-    let unit_grid_size = image_dimensions.0 / info.grid;
-    
-    let info = calculate(unit_grid_size, info.grid, image_dimensions.0, info);
+    let grid = info.grid;
+    let info = calculate(grid, info);
 
-    TextureMap { base, normal, info }
+    TextureMap { base, normal, info, grid }
 }
 
 #[inline]
-fn calculate(unit_grid_size: u32, grid_size: u32, total_side_length: u32, info: TextureMapInfo) -> Arc<HashMap<u16, [[[f32;2];4];6]>> {
+fn calculate(grid_size: u32, info: TextureMapInfo) -> Arc<HashMap<u16, [[[f32;2];4];6]>> {
     let mut map = HashMap::new();
     for (id, unit) in info.blocks {
         let mut faces = [[[0.0;2];4];6];
         for (i, loc) in unit.loc.iter().enumerate() {
-            let (y, x) = ((loc / grid_size) as u32, (loc % grid_size) as u32);
-            let (min_x, min_y, max_x, max_y) = (x as f32 * unit_grid_size as f32 / total_side_length as f32, y as f32 * unit_grid_size as f32 / total_side_length as f32, (x+1) as f32 * unit_grid_size as f32 / total_side_length as f32, (y+1) as f32 * unit_grid_size as f32 / total_side_length as f32);
-            faces[i] = [[max_x, min_y], [max_x, max_y], [min_x, max_y], [min_x, min_y]];
+            faces[i] = loc_tex_coords(*loc, grid_size);
         }
         map.insert(id, faces);
     }
 
     Arc::new(map)
+}
+
+/// Locations count from the bottom left of the atlas, since the image is flipped when it is loaded
+fn loc_tex_coords(loc: u32, grid_size: u32) -> [[f32; 2]; 4] {
+    let (y, x) = (loc / grid_size, loc % grid_size);
+    let unit = 1.0 / grid_size as f32;
+    let (min_x, min_y, max_x, max_y) = (x as f32 * unit, y as f32 * unit, (x + 1) as f32 * unit, (y + 1) as f32 * unit);
+    [[max_x, min_y], [max_x, max_y], [min_x, max_y], [min_x, min_y]]
 }
\ No newline at end of file
@@ -0,0 +1,84 @@
+use std::time::Duration;
+
+/// Number of game ticks per second
+pub const TICK_RATE: u32 = 60;
+/// Seconds of game time a tick simulates
+pub const TICK_DELTA: f32 = 1.0 / TICK_RATE as f32;
+/// Most ticks run for one frame. Time past that is dropped, so the game slows down instead of
+/// falling further behind when ticks take longer than they simulate
+const MAX_TICKS_PER_FRAME: u32 = 5;
+/// Time the tick rate is measured over
+const RATE_WINDOW: Duration = Duration::from_millis(250);
+
+/// Runs the simulation at a fixed rate, independent of the frame rate. Time passed between frames is
+/// accumulated and spent in ticks of `TICK_DELTA`
+#[derive(Default)]
+pub struct Timestep {
+    accumulator: Duration,
+    /// Ticks and time since the tick rate was last measured
+    window_ticks: u32,
+    window_time: Duration,
+    tick_rate: f32,
+}
+
+impl Timestep {
+    pub fn new() -> Timestep {
+        Timestep::default()
+    }
+
+    /// Adds the time passed since the last frame and returns the number of ticks to run
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        let tick = Duration::from_secs_f32(TICK_DELTA);
+        self.accumulator = (self.accumulator + elapsed).min(tick * MAX_TICKS_PER_FRAME);
+        let ticks = (self.accumulator.as_nanos() / tick.as_nanos()) as u32;
+        self.accumulator -= tick * ticks;
+
+        self.window_ticks += ticks;
+        self.window_time += elapsed;
+        if self.window_time >= RATE_WINDOW {
+            self.tick_rate = self.window_ticks as f32 / self.window_time.as_secs_f32();
+            self.window_ticks = 0;
+            self.window_time = Duration::ZERO;
+        }
+        ticks
+    }
+
+    /// Returns how far the time is between the last tick and the next one, from 0 to 1.
+    /// Used to interpolate what is rendered between the states of the last two ticks
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator.as_secs_f32() / TICK_DELTA).min(1.0)
+    }
+
+    /// Returns the number of ticks run per second, measured over the last quarter second
+    pub fn tick_rate(&self) -> f32 {
+        self.tick_rate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_are_independent_of_frame_rate() {
+        for fps in [30, 60, 144, 1000] {
+            let mut timestep = Timestep::new();
+            let frame = Duration::from_secs(1) / fps;
+            let ticks: u32 = (0..fps).map(|_| timestep.advance(frame)).sum();
+            assert!((59..=60).contains(&ticks), "{} ticks at {} fps", ticks, fps);
+            assert!((0.0..=1.0).contains(&timestep.alpha()));
+            assert!((timestep.tick_rate() - 60.0).abs() < 5.0);
+        }
+    }
+
+    #[test]
+    fn long_frames_run_limited_ticks() {
+        let mut timestep = Timestep::new();
+        assert_eq!(timestep.advance(Duration::from_secs(2)), MAX_TICKS_PER_FRAME);
+        assert_eq!(timestep.advance(Duration::ZERO), 0);
+        assert_eq!(timestep.alpha(), 0.0);
+
+        assert_eq!(timestep.advance(Duration::from_secs_f32(TICK_DELTA * 1.5)), 1);
+        assert!((timestep.alpha() - 0.5).abs() < 0.01);
+    }
+}
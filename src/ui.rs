@@ -0,0 +1,413 @@
+use imgui::{Condition, Drag, MouseButton, ProgressBar, Slider, StyleColor, Ui, Window};
+
+use crate::block_entity::{BlockEntity, Furnace};
+use crate::command;
+use crate::crafting::CraftingGrid;
+use crate::inventory::{Inventory, ItemStack, HOTBAR_SIZE};
+use crate::loader::LoadShape;
+use crate::world::World;
+use crate::player::Player;
+use crate::registry::{ItemRegistry, Registry};
+use crate::settings::{Settings, MSAA_SAMPLES};
+
+const SLOT_SIZE: [f32; 2] = [72.0, 32.0];
+/// Slots of the hotbar are taller, to fit the number of the slot above the item
+const HOTBAR_SLOT_SIZE: [f32; 2] = [72.0, 44.0];
+const SELECTED_SLOT_COLOR: [f32; 4] = [0.85, 0.6, 0.15, 1.0];
+
+/// State of the in-game inventory window
+#[derive(Default)]
+pub struct InventoryUi {
+    /// Slot picked up by the last left click
+    held: Option<usize>,
+}
+
+impl InventoryUi {
+    /// Draws the inventory window and the crafting table or container window if one is open
+    pub fn draw(&mut self, ui: &Ui, player: &mut Player, world: &mut World, registry: &Registry) {
+        self.draw_inventory(ui, player, registry);
+
+        if let Some(pos) = player.open_container {
+            let mut opened = true;
+            let window = Window::new("Container")
+                .opened(&mut opened)
+                .resizable(false)
+                .always_auto_resize(true);
+            if let Some(tok) = window.begin(ui) {
+                let inventory = &mut player.inventory;
+                let found = world.with_block_entity_mut(pos, |entity| {
+                    draw_block_entity(ui, entity, inventory, registry)
+                });
+                if found.is_none() {
+                    opened = false;
+                }
+                tok.end();
+            }
+            if !opened {
+                player.open_container = None;
+            }
+        }
+
+        if let Some(grid) = player.crafting_table.as_mut() {
+            let mut opened = true;
+            let window = Window::new("Crafting Table")
+                .opened(&mut opened)
+                .resizable(false)
+                .always_auto_resize(true);
+            if let Some(tok) = window.begin(ui) {
+                draw_crafting_grid(ui, "table", grid, &mut player.inventory, registry);
+                tok.end();
+            }
+            if !opened {
+                player.close_crafting_table(world);
+            }
+        }
+    }
+
+    /// Draws the inventory slots and crafting grid. Left click picks up a slot and drops it on
+    /// the next clicked slot, merging equal items and swapping otherwise. Right click splits a stack
+    fn draw_inventory(&mut self, ui: &Ui, player: &mut Player, registry: &Registry) {
+        let inventory = &mut player.inventory;
+        let items = &registry.items;
+        let window = Window::new("Inventory")
+            .resizable(false)
+            .always_auto_resize(true);
+        let tok = match window.begin(ui) {
+            Some(tok) => tok,
+            None => return,
+        };
+
+        for slot in 0..inventory.num_slots() {
+            if slot % HOTBAR_SIZE != 0 {
+                ui.same_line();
+            }
+
+            let mut label = slot_label(inventory.get(slot), items);
+            if self.held == Some(slot) {
+                label = format!("[{}]", label);
+            } else if slot == inventory.selected() {
+                label = format!("> {}", label);
+            }
+
+            if ui.button_with_size(format!("{}##slot{}", label, slot), SLOT_SIZE) {
+                match self.held.take() {
+                    None => {
+                        if inventory.get(slot).is_some() {
+                            self.held = Some(slot);
+                        }
+                    }
+                    Some(held) => {
+                        let same_item = match (inventory.get(held), inventory.get(slot)) {
+                            (Some(a), Some(b)) => a.id() == b.id(),
+                            _ => false,
+                        };
+                        if same_item {
+                            inventory.merge(held, slot);
+                        } else {
+                            inventory.swap(held, slot);
+                        }
+                    }
+                }
+            }
+            if ui.is_item_clicked_with_button(MouseButton::Right) {
+                inventory.split(slot);
+            }
+        }
+
+        ui.separator();
+        draw_crafting_grid(ui, "inventory", &mut player.crafting_grid, inventory, registry);
+
+        tok.end();
+    }
+}
+
+/// Draws the hotbar at the bottom of the screen, highlighting the selected slot. Clicking a slot selects it
+pub fn draw_hotbar(ui: &Ui, inventory: &mut Inventory, items: &ItemRegistry) {
+    let [width, height] = ui.io().display_size;
+    let window = Window::new("Hotbar")
+        .title_bar(false)
+        .resizable(false)
+        .movable(false)
+        .always_auto_resize(true)
+        .position([width / 2.0, height - 8.0], Condition::Always)
+        .position_pivot([0.5, 1.0]);
+    let tok = match window.begin(ui) {
+        Some(tok) => tok,
+        None => return,
+    };
+
+    for slot in 0..HOTBAR_SIZE.min(inventory.num_slots()) {
+        if slot > 0 {
+            ui.same_line();
+        }
+        let color = (slot == inventory.selected())
+            .then(|| ui.push_style_color(StyleColor::Button, SELECTED_SLOT_COLOR));
+        let label = format!("{}\n{}##hotbar{}", slot + 1, slot_label(inventory.get(slot), items), slot);
+        if ui.button_with_size(label, HOTBAR_SLOT_SIZE) {
+            inventory.select(slot);
+        }
+        if let Some(color) = color {
+            color.pop();
+        }
+    }
+
+    tok.end();
+}
+
+/// Draws a crafting grid and its output. Left clicking a cell moves one item of the selected
+/// hotbar stack into it, right clicking a cell moves its items back into the inventory
+fn draw_crafting_grid(
+    ui: &Ui,
+    id: &str,
+    grid: &mut CraftingGrid,
+    inventory: &mut Inventory,
+    registry: &Registry,
+) {
+    let items = &registry.items;
+    for y in 0..grid.size() {
+        for x in 0..grid.size() {
+            if x != 0 {
+                ui.same_line();
+            }
+            let label = slot_label(grid.get(x, y), items);
+            if ui.button_with_size(format!("{}##{}{}_{}", label, id, x, y), SLOT_SIZE) {
+                let fits = match (grid.get(x, y), inventory.selected_stack()) {
+                    (_, None) => false,
+                    (None, Some(_)) => true,
+                    (Some(cell), Some(selected)) => {
+                        cell.id() == selected.id() && cell.count() < cell.max_stack()
+                    }
+                };
+                if fits {
+                    if let Some(mut one) = inventory.remove(inventory.selected(), 1) {
+                        let cell = match grid.set(x, y, None) {
+                            None => one,
+                            Some(mut cell) => {
+                                cell.merge(&mut one);
+                                cell
+                            }
+                        };
+                        grid.set(x, y, Some(cell));
+                    }
+                }
+            }
+            if ui.is_item_clicked_with_button(MouseButton::Right) {
+                if let Some(stack) = grid.set(x, y, None) {
+                    grid.set(x, y, inventory.add(stack));
+                }
+            }
+        }
+    }
+
+    let output = grid.preview(&registry.recipes, items);
+    ui.text(format!("Output: {}", slot_label(output.as_ref(), items)));
+    if let Some(output) = output {
+        // Only craft when the output fits, so the ingredients are never used up for nothing
+        let full = !inventory.can_add(&output);
+        let disabled = ui.begin_disabled(full);
+        if ui.button(format!("Craft##{}", id)) {
+            if let Some(stack) = grid.craft(&registry.recipes, items) {
+                inventory.add(stack);
+            }
+        }
+        disabled.end();
+        if full {
+            ui.same_line();
+            ui.text("Inventory full");
+        }
+    }
+}
+
+/// Draws the contents of a block entity. Returns true if they changed
+fn draw_block_entity(ui: &Ui, entity: &mut BlockEntity, inventory: &mut Inventory, registry: &Registry) -> bool {
+    let items = &registry.items;
+    let mut changed = false;
+    match entity {
+        BlockEntity::Chest(chest) => {
+            // Clicking a chest slot moves its items into the inventory
+            for slot in 0..chest.num_slots() {
+                if slot % HOTBAR_SIZE != 0 {
+                    ui.same_line();
+                }
+                let label = slot_label(chest.get(slot), items);
+                if ui.button_with_size(format!("{}##chest{}", label, slot), SLOT_SIZE) {
+                    if let Some(stack) = chest.take(slot) {
+                        if let Some(leftover) = inventory.add(stack) {
+                            chest.add(leftover);
+                        }
+                        changed = true;
+                    }
+                }
+            }
+            if ui.button("Store selected") {
+                if let Some(stack) = inventory.take(inventory.selected()) {
+                    if let Some(leftover) = chest.add(stack) {
+                        inventory.add(leftover);
+                    }
+                    changed = true;
+                }
+            }
+        }
+        BlockEntity::Furnace(furnace) => changed = draw_furnace(ui, furnace, inventory, registry),
+        BlockEntity::Sign(text) => {
+            changed = ui.input_text("Text##sign", text).build();
+        }
+    }
+    changed
+}
+
+/// Draws the input and output slots and the progress of a furnace. Left clicking the input slot moves
+/// the selected hotbar stack into it, right clicking it or clicking the output moves items into the inventory.
+/// Returns true if the furnace changed
+fn draw_furnace(ui: &Ui, furnace: &mut Furnace, inventory: &mut Inventory, registry: &Registry) -> bool {
+    let items = &registry.items;
+    let mut changed = false;
+    let label = slot_label(furnace.input.as_ref(), items);
+    if ui.button_with_size(format!("{}##furnace_input", label), SLOT_SIZE) {
+        if let Some(mut stack) = inventory.take(inventory.selected()) {
+            changed = true;
+            match furnace.input.as_mut() {
+                None => furnace.input = Some(stack),
+                Some(input) => {
+                    input.merge(&mut stack);
+                    if stack.count() > 0 {
+                        inventory.add(stack);
+                    }
+                }
+            }
+        }
+    }
+    if ui.is_item_clicked_with_button(MouseButton::Right) {
+        if let Some(stack) = furnace.input.take() {
+            furnace.input = inventory.add(stack);
+            furnace.progress = 0.0;
+            changed = true;
+        }
+    }
+
+    ui.same_line();
+    let fraction = furnace
+        .duration(registry)
+        .map(|duration| furnace.progress / duration)
+        .unwrap_or(0.0);
+    ProgressBar::new(fraction).size(SLOT_SIZE).build(ui);
+
+    ui.same_line();
+    let label = slot_label(furnace.output.as_ref(), items);
+    if ui.button_with_size(format!("{}##furnace_output", label), SLOT_SIZE) {
+        if let Some(stack) = furnace.output.take() {
+            furnace.output = inventory.add(stack);
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Draws the settings window, saving the settings to `path` when asked to. Returns true if a setting was changed
+pub fn draw_settings(ui: &Ui, settings: &mut Settings, path: &str) -> bool {
+    const SHAPES: [LoadShape; 3] = [LoadShape::Sphere, LoadShape::Cylinder, LoadShape::Cube];
+    let window = Window::new("Settings")
+        .resizable(false)
+        .always_auto_resize(true);
+    let tok = match window.begin(ui) {
+        Some(tok) => tok,
+        None => return false,
+    };
+    let mut changed = false;
+
+    let distance = &mut settings.render_distance;
+    let mut shape = SHAPES.iter().position(|shape| *shape == distance.shape).unwrap_or(0);
+    if ui.combo_simple_string("Render shape", &mut shape, &["Sphere", "Cylinder", "Cube"]) {
+        distance.shape = SHAPES[shape];
+        changed = true;
+    }
+    changed |= Slider::new("Render distance", 1, 64).build(ui, &mut distance.horizontal);
+    changed |= Slider::new("Vertical render distance", 1, 32).build(ui, &mut distance.vertical);
+    changed |= Slider::new("Chunk threads (0 = auto)", 0, 32).build(ui, &mut settings.chunk_threads);
+    changed |= Slider::new("Mesh threads (0 = auto)", 0, 32).build(ui, &mut settings.mesh_threads);
+    changed |= Slider::new("FOV", 30.0, 120.0).build(ui, &mut settings.fov);
+    changed |= Slider::new("Mouse sensitivity", 0.05, 10.0).build(ui, &mut settings.rot_speed);
+    changed |= Slider::new("Anisotropy", 1, 32).build(ui, &mut settings.anisotropy);
+    changed |= Slider::new("Window width", 320, 3840).build(ui, &mut settings.window_width);
+    changed |= Slider::new("Window height", 240, 2160).build(ui, &mut settings.window_height);
+
+    ui.separator();
+    ui.text("Applied on restart:");
+    changed |= Drag::new("Seed").build(ui, &mut settings.seed);
+    let labels = MSAA_SAMPLES.map(|samples| match samples {
+        0 => "Off".to_string(),
+        samples => format!("{}x", samples),
+    });
+    let mut msaa = MSAA_SAMPLES.iter().position(|samples| *samples == settings.msaa).unwrap_or(0);
+    if ui.combo_simple_string("MSAA", &mut msaa, &labels) {
+        settings.msaa = MSAA_SAMPLES[msaa];
+        changed = true;
+    }
+
+    ui.separator();
+    if ui.button("Save") {
+        settings.save(path);
+    }
+    ui.same_line();
+    if ui.button("Reset to defaults") {
+        *settings = Settings::default();
+        changed = true;
+    }
+
+    tok.end();
+    changed
+}
+
+/// State of the console window commands are typed in, see `command::run`
+#[derive(Default)]
+pub struct Console {
+    input: String,
+    /// Message of the last command that was run
+    output: String,
+}
+
+impl Console {
+    /// Draws the console window and runs the typed command when enter is pressed
+    pub fn draw(&mut self, ui: &Ui, world: &mut World) {
+        let window = Window::new("Console")
+            .resizable(false)
+            .always_auto_resize(true);
+        let tok = match window.begin(ui) {
+            Some(tok) => tok,
+            None => return,
+        };
+        if ui
+            .input_text("##command", &mut self.input)
+            .hint("/time set noon")
+            .enter_returns_true(true)
+            .build()
+        {
+            self.output = match command::run(&self.input, world) {
+                Ok(message) => message,
+                Err(e) => e,
+            };
+            self.input.clear();
+        }
+        if !self.output.is_empty() {
+            ui.text(&self.output);
+        }
+        tok.end();
+    }
+}
+
+/// Returns the text shown on a slot button
+pub fn slot_label(stack: Option<&ItemStack>, items: &ItemRegistry) -> String {
+    match stack {
+        None => String::new(),
+        Some(stack) => {
+            let name = items
+                .get(stack.id())
+                .map(|info| info.name.as_str())
+                .unwrap_or("?");
+            match stack.durability() {
+                Some(durability) => format!("{} ({})", name, durability),
+                None => format!("{} x{}", name, stack.count()),
+            }
+        }
+    }
+}
@@ -0,0 +1,307 @@
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::scheduler::JobQueue;
+
+/// Time utilisation is averaged over
+const UTILISATION_WINDOW: Duration = Duration::from_secs(1);
+/// Value of `Counters::job_start` while a worker waits for a job
+const IDLE: u64 = u64::MAX;
+
+/// Returns the number of threads a pool uses by default: half of the available cores,
+/// so the chunk and mesh pools together leave room for the main thread
+pub fn default_threads() -> usize {
+    let cores = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(2);
+    (cores / 2).max(1)
+}
+
+/// Counters of a worker thread of a `WorkerPool`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WorkerStats {
+    /// Number of jobs finished, including the ones that panicked
+    pub jobs: u64,
+    /// Number of jobs that panicked
+    pub panics: u64,
+    /// Fraction of the last second spent working on jobs
+    pub utilisation: f32,
+}
+
+/// Threads running a function on the jobs of a queue. Threads survive jobs that panic, handing the job
+/// to a callback so its owner can clean up after it. Threads are stopped and joined when the pool is dropped
+pub struct WorkerPool<T> {
+    name: String,
+    queue: JobQueue<T>,
+    work: Arc<dyn Fn(&T) + Send + Sync>,
+    on_panic: Arc<dyn Fn(T) + Send + Sync>,
+    workers: Vec<Worker>,
+    /// Time the busy times of the workers are measured from
+    start: Instant,
+    sample: Mutex<Sample>,
+}
+
+struct Worker {
+    stop: Arc<AtomicBool>,
+    counters: Arc<Counters>,
+    handle: Option<JoinHandle<()>>,
+}
+
+#[derive(Default)]
+struct Counters {
+    jobs: AtomicU64,
+    panics: AtomicU64,
+    /// Nanoseconds spent on finished jobs
+    busy: AtomicU64,
+    /// Nanoseconds since the start of the pool the current job started at, `IDLE` if there is none
+    job_start: AtomicU64,
+}
+
+/// Busy times of the workers at the start of the current utilisation window
+struct Sample {
+    time: u64,
+    busy: Vec<u64>,
+    utilisation: Vec<f32>,
+}
+
+impl<T: Send + 'static> WorkerPool<T> {
+    /// Starts `threads` threads calling `work` with the jobs popped from `queue`.
+    /// `on_panic` is called with the jobs `work` panicked on
+    pub(crate) fn new(
+        name: &str,
+        threads: usize,
+        queue: JobQueue<T>,
+        work: impl Fn(&T) + Send + Sync + 'static,
+        on_panic: impl Fn(T) + Send + Sync + 'static,
+    ) -> WorkerPool<T> {
+        let mut pool = WorkerPool {
+            name: name.to_string(),
+            queue,
+            work: Arc::new(work),
+            on_panic: Arc::new(on_panic),
+            workers: Vec::new(),
+            start: Instant::now(),
+            sample: Mutex::new(Sample {
+                time: 0,
+                busy: Vec::new(),
+                utilisation: Vec::new(),
+            }),
+        };
+        pool.set_threads(threads);
+        pool
+    }
+
+    /// Starts or stops threads until there are `threads` of them (at least 1).
+    /// Stopped threads finish their current job first
+    pub fn set_threads(&mut self, threads: usize) {
+        let threads = threads.max(1);
+        while self.workers.len() < threads {
+            match self.spawn(self.workers.len()) {
+                Ok(worker) => self.workers.push(worker),
+                Err(e) => {
+                    println!("Error starting {} worker: {}", self.name, e);
+                    break;
+                }
+            }
+        }
+        let stopped = self.workers.split_off(threads.min(self.workers.len()));
+        self.stop(stopped);
+    }
+
+    pub fn threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Returns the counters of every thread, with utilisation averaged over the last second
+    pub fn stats(&self) -> Vec<WorkerStats> {
+        let now = self.start.elapsed().as_nanos() as u64;
+        let busy: Vec<u64> = self.workers.iter().map(|w| w.counters.busy_time(now)).collect();
+
+        let mut sample = self.sample.lock().unwrap();
+        let elapsed = now - sample.time;
+        if elapsed >= UTILISATION_WINDOW.as_nanos() as u64 || sample.busy.len() != busy.len() {
+            sample.utilisation = busy
+                .iter()
+                .enumerate()
+                .map(|(i, busy)| match sample.busy.get(i) {
+                    Some(previous) if elapsed > 0 => (busy.saturating_sub(*previous) as f32 / elapsed as f32).min(1.0),
+                    _ => 0.0,
+                })
+                .collect();
+            sample.busy = busy;
+            sample.time = now;
+        }
+
+        self.workers
+            .iter()
+            .zip(&sample.utilisation)
+            .map(|(worker, utilisation)| WorkerStats {
+                jobs: worker.counters.jobs.load(Ordering::Relaxed),
+                panics: worker.counters.panics.load(Ordering::Relaxed),
+                utilisation: *utilisation,
+            })
+            .collect()
+    }
+
+    fn spawn(&self, index: usize) -> std::io::Result<Worker> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let counters = Arc::new(Counters::default());
+        counters.job_start.store(IDLE, Ordering::Relaxed);
+
+        let name = format!("{} worker {}", self.name, index);
+        let handle = {
+            let (stop, counters) = (stop.clone(), counters.clone());
+            let (queue, work, on_panic, start) = (self.queue.clone(), self.work.clone(), self.on_panic.clone(), self.start);
+            let name = name.clone();
+            std::thread::Builder::new().name(name.clone()).spawn(move || {
+                while let Some(job) = queue.pop(&stop) {
+                    let job_start = start.elapsed().as_nanos() as u64;
+                    counters.job_start.store(job_start, Ordering::Relaxed);
+
+                    // Keep the thread alive if a job panics, so one bad chunk doesn't stop loading
+                    if let Err(panic) = std::panic::catch_unwind(AssertUnwindSafe(|| work(&job))) {
+                        counters.panics.fetch_add(1, Ordering::Relaxed);
+                        println!("{} panicked: {}", name, panic_message(&panic));
+                        on_panic(job);
+                    }
+
+                    let job_end = start.elapsed().as_nanos() as u64;
+                    counters.busy.fetch_add(job_end - job_start, Ordering::Relaxed);
+                    counters.job_start.store(IDLE, Ordering::Relaxed);
+                    counters.jobs.fetch_add(1, Ordering::Relaxed);
+                }
+            })?
+        };
+
+        Ok(Worker {
+            stop,
+            counters,
+            handle: Some(handle),
+        })
+    }
+}
+
+impl<T> WorkerPool<T> {
+    fn stop(&self, workers: Vec<Worker>) {
+        for worker in &workers {
+            worker.stop.store(true, Ordering::Relaxed);
+        }
+        self.queue.wake_all();
+        for mut worker in workers {
+            if let Some(handle) = worker.handle.take() {
+                match handle.join() {
+                    Ok(_) => (),
+                    Err(_) => println!("Error joining {} worker", self.name),
+                }
+            }
+        }
+    }
+}
+
+impl<T> Drop for WorkerPool<T> {
+    fn drop(&mut self) {
+        let workers = std::mem::take(&mut self.workers);
+        self.stop(workers);
+    }
+}
+
+impl Counters {
+    /// Returns the nanoseconds spent on jobs, including the current one
+    fn busy_time(&self, now: u64) -> u64 {
+        let current = match self.job_start.load(Ordering::Relaxed) {
+            IDLE => 0,
+            start => now.saturating_sub(start),
+        };
+        self.busy.load(Ordering::Relaxed) + current
+    }
+}
+
+fn panic_message(panic: &Box<dyn std::any::Any + Send>) -> &str {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn workers_survive_panics() {
+        let queue = JobQueue::new(16);
+        let (tx, rx) = mpsc::channel();
+        let (panic_tx, panic_rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let panic_tx = Mutex::new(panic_tx);
+        let pool = WorkerPool::new(
+            "test",
+            2,
+            queue.clone(),
+            move |job: &u32| {
+                if *job == 0 {
+                    panic!("bad job");
+                }
+                tx.lock().unwrap().send(*job).unwrap();
+            },
+            move |job| panic_tx.lock().unwrap().send(job).unwrap(),
+        );
+
+        for job in [0, 1, 0, 2, 3] {
+            queue.push(job, 0.0);
+        }
+        let mut done: Vec<u32> = (0..3).map(|_| rx.recv_timeout(Duration::from_secs(10)).unwrap()).collect();
+        done.sort();
+        assert_eq!(done, [1, 2, 3]);
+        // The jobs that panicked are handed back
+        for _ in 0..2 {
+            assert_eq!(panic_rx.recv_timeout(Duration::from_secs(10)), Ok(0));
+        }
+
+        // Jobs are counted right after they finish
+        let start = Instant::now();
+        while pool.stats().iter().map(|stats| stats.jobs).sum::<u64>() < 5 {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let stats = pool.stats();
+        assert_eq!(stats.len(), 2);
+        assert!(stats.iter().all(|stats| stats.utilisation <= 1.0));
+        assert_eq!(stats.iter().map(|stats| stats.jobs).sum::<u64>(), 5);
+        assert_eq!(stats.iter().map(|stats| stats.panics).sum::<u64>(), 2);
+    }
+
+    #[test]
+    fn pool_resizes_and_stops_on_drop() {
+        let queue = JobQueue::new(16);
+        let running = Arc::new(AtomicU64::new(0));
+        let mut pool = {
+            let running = running.clone();
+            WorkerPool::new(
+                "test",
+                3,
+                queue.clone(),
+                move |_: &()| {
+                    running.fetch_add(1, Ordering::Relaxed);
+                },
+                |_| (),
+            )
+        };
+        assert_eq!(pool.threads(), 3);
+        pool.set_threads(1);
+        assert_eq!(pool.threads(), 1);
+        pool.set_threads(0);
+        assert_eq!(pool.threads(), 1);
+
+        drop(pool);
+        // No thread is left to run the job
+        queue.push((), 0.0);
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(running.load(Ordering::Relaxed), 0);
+    }
+}
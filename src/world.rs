@@ -0,0 +1,375 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use parry3d::bounding_volume::BoundingVolume;
+use serde::{Deserialize, Serialize};
+
+use crate::actions::Actions;
+use crate::block_entity::BlockEntity;
+use crate::chunk::Block;
+use crate::daylight::{self, DAY_LENGTH, START_TIME_OF_DAY};
+use crate::file_util::{read_from_file, save_to_file};
+use crate::input::Input;
+use crate::inventory::ItemStack;
+use crate::item_entity::{self, ItemEntity, Rng, PICKUP_RADIUS};
+use crate::loader::{in_distance, ChunkLoader, LoadDistance, LoadShape};
+use crate::player::Player;
+use crate::pos::{BlockPos, ChunkPos};
+use crate::raycast::{self, RayHit};
+use crate::registry::{BlockInfo, Registry};
+
+/// Seconds a dropped item stays in the world before it despawns
+const DEFAULT_ITEM_DESPAWN_TIME: f32 = 300.0;
+
+/// State of the world that isn't part of a chunk, saved next to the chunks
+#[derive(Debug, Serialize, Deserialize)]
+struct WorldMeta {
+    time: f64,
+}
+
+impl Default for WorldMeta {
+    fn default() -> Self {
+        WorldMeta {
+            time: START_TIME_OF_DAY as f64 * DAY_LENGTH,
+        }
+    }
+}
+
+impl WorldMeta {
+    /// Loads the metadata of a world. Returns the default for new worlds or if the file is invalid
+    fn load(path: &str) -> WorldMeta {
+        let data = match read_from_file(path) {
+            Some(data) => data,
+            None => return WorldMeta::default(),
+        };
+        match bincode::deserialize(data.as_slice()) {
+            Ok(meta) => meta,
+            Err(e) => {
+                println!("Error reading world data: {}", e);
+                WorldMeta::default()
+            }
+        }
+    }
+
+    fn save(&self, path: &str) {
+        match bincode::serialize(self) {
+            Ok(bytes) => {
+                save_to_file(bytes.as_slice(), path);
+            }
+            Err(e) => println!("Error serializing world data: {}", e),
+        }
+    }
+}
+
+/// Holds the state of the world: its chunks, block entities and dropped items.
+/// Doesn't need a window, so it can be used in tests and on servers
+pub struct World {
+    loader: ChunkLoader,
+    registry: Arc<Registry>,
+    /// Distance from camera that AI and physics are updated
+    simulation_distance: LoadDistance,
+
+    item_rng: Rng,
+    item_despawn_time: f32,
+
+    /// Seconds of game time since the world was created, see `daylight`
+    time: f64,
+    meta_path: String,
+}
+
+impl World {
+    /// Creates a new world with seed that saves its chunks and time to `save_dir`
+    pub fn new(seed: u32, registry: Arc<Registry>, save_dir: &str) -> World {
+        let meta_path = format!("{}/world.dat", save_dir);
+        let meta = WorldMeta::load(&meta_path);
+        World {
+            loader: ChunkLoader::new(seed, save_dir),
+            registry,
+            simulation_distance: LoadDistance::new(LoadShape::Cylinder, 4, 4),
+            item_rng: Rng::new(seed as u64),
+            item_despawn_time: DEFAULT_ITEM_DESPAWN_TIME,
+            time: meta.time,
+            meta_path,
+        }
+    }
+
+    /// Loads and unloads chunks around the player. To be called once per game tick
+    pub fn update(&mut self, player: &Player) {
+        self.loader.update(player);
+    }
+
+    /// Runs a game tick: updates the actions from the input, moves the player and updates the world around it.
+    /// The game and replays both use it, so recorded input plays back the same way
+    pub fn tick(&mut self, player: &mut Player, actions: &mut Actions, input: &Input, delta: f32) {
+        actions.update(input);
+        player.look(input, delta);
+        self.update(player);
+        player.update(delta, actions, self);
+        self.tick_block_entities(delta);
+        self.tick_items(delta, player);
+        self.time += delta as f64;
+    }
+
+    /// Returns the seconds of game time since the world was created
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Returns the time of day, from 0 at midnight to 0.5 at noon and back to 1
+    pub fn time_of_day(&self) -> f32 {
+        daylight::time_of_day(self.time)
+    }
+
+    /// Skips ahead to the next time it is `time_of_day`, so the time never goes backwards
+    pub fn set_time_of_day(&mut self, time_of_day: f32) {
+        let day = (self.time / DAY_LENGTH).floor();
+        let mut time = (day + time_of_day.rem_euclid(1.0) as f64) * DAY_LENGTH;
+        if time < self.time {
+            time += DAY_LENGTH;
+        }
+        self.time = time;
+    }
+
+    /// Makes updates wait until the queued chunks are loaded, so the same input always sees the same chunks
+    pub fn set_blocking_loads(&mut self, blocking: bool) {
+        self.loader.set_blocking(blocking);
+    }
+
+    /// Returns a hash of the blocks of every loaded chunk. It doesn't depend on the order chunks were loaded in
+    /// or on the platform, so it can be compared with hashes saved to files
+    pub fn hash_blocks(&self) -> u64 {
+        let mut coords: Vec<&ChunkPos> = self.loader.chunks().keys().collect();
+        coords.sort_by_key(|coord| (coord.x, coord.y, coord.z));
+
+        // 64 bit FNV-1a
+        let mut hash: u64 = 0xcbf29ce484222325;
+        let mut write = |value: u32| {
+            for byte in value.to_le_bytes() {
+                hash = (hash ^ byte as u64).wrapping_mul(0x100000001b3);
+            }
+        };
+        for coord in coords {
+            write(coord.x as u32);
+            write(coord.y as u32);
+            write(coord.z as u32);
+            let chunk = self.loader.chunk(coord).unwrap().read().unwrap();
+            match chunk.data() {
+                None => write(u32::MAX),
+                Some(data) => data.iter().for_each(|block| write(block.id() as u32)),
+            }
+        }
+        hash
+    }
+
+    /// Returns the chunk loader holding the loaded chunks
+    pub fn loader(&self) -> &ChunkLoader {
+        &self.loader
+    }
+
+    /// Sets the area around the player that chunks are loaded in
+    pub fn set_load_distance(&mut self, distance: LoadDistance) {
+        self.loader.set_load_distance(distance);
+    }
+
+    /// Sets the number of threads loading and generating chunks
+    pub fn set_worker_threads(&mut self, threads: usize) {
+        self.loader.set_worker_threads(threads);
+    }
+
+    /// Returns the registry of items, blocks and recipes
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Returns block data based on coordinate (world space). Returns none if block is in unloaded chunk
+    pub fn get_block(&self, pos: BlockPos) -> Option<Block> {
+        self.loader.get_block(pos)
+    }
+
+    /// Returns the first block along a ray (world space) within `max_distance` that isn't air and that `hits`
+    /// accepts, like `|info| !info.fluid` to look through fluids. Blocks missing from the registry are always hit.
+    /// The ray stops at unloaded chunks
+    pub fn raycast(
+        &self,
+        origin: [f64; 3],
+        direction: [f32; 3],
+        max_distance: f32,
+        hits: impl Fn(&BlockInfo) -> bool,
+    ) -> Option<RayHit> {
+        raycast::raycast(origin, direction, max_distance, |pos| {
+            let block = self.get_block(pos)?;
+            Some(!block.is_air() && self.registry.blocks.get(block.id()).map(&hits).unwrap_or(true))
+        })
+    }
+
+    /// Sets block, creating the block entity of the new block and removing the old one.
+    /// Returns the removed block entity, if any
+    pub fn set_block(&self, pos: BlockPos, block: Block) -> Option<BlockEntity> {
+        let (chunk_coord, local) = pos.split();
+        let chunk = self.loader.chunk(&chunk_coord)?;
+        let entity = self
+            .registry
+            .blocks
+            .get(block.id())
+            .and_then(|info| info.block_entity)
+            .map(BlockEntity::new);
+
+        let mut chunk = chunk.write().unwrap();
+        if !chunk.set_block(local, block) {
+            return None;
+        }
+        chunk.set_modified();
+        let removed = chunk.set_block_entity(local, entity);
+        drop(chunk);
+
+        // Only the neighbors sharing a face the block is on have to be remeshed
+        for (neighbor, on_border) in chunk_coord.neighbors().iter().zip(local.on_border()) {
+            if !on_border {
+                continue;
+            }
+            if let Some(neighbor) = self.loader.chunk(neighbor) {
+                neighbor.write().unwrap().request_update();
+            }
+        }
+        removed
+    }
+
+    /// Returns true if there is a block entity at a coordinate (world space)
+    pub fn has_block_entity(&self, pos: BlockPos) -> bool {
+        let (chunk_coord, local) = pos.split();
+        match self.loader.chunk(&chunk_coord) {
+            None => false,
+            Some(chunk) => chunk.read().unwrap().get_block_entity(local).is_some(),
+        }
+    }
+
+    /// Calls `f` with the block entity at a coordinate (world space). `f` returns true if it changed the
+    /// block entity, which marks its chunk as modified. Returns what `f` returned, or none if there is no block entity
+    pub fn with_block_entity_mut(
+        &self,
+        pos: BlockPos,
+        f: impl FnOnce(&mut BlockEntity) -> bool,
+    ) -> Option<bool> {
+        let (chunk_coord, local) = pos.split();
+        let chunk = self.loader.chunk(&chunk_coord)?;
+        let mut chunk = chunk.write().unwrap();
+        let changed = f(chunk.get_block_entity_mut(local)?);
+        if changed {
+            chunk.set_modified();
+        }
+        Some(changed)
+    }
+
+    /// Updates the block entities of every loaded chunk
+    pub fn tick_block_entities(&self, delta: f32) {
+        for chunk in self.loader.chunks().values() {
+            if !chunk.read().unwrap().has_block_entities() {
+                continue;
+            }
+            let mut chunk = chunk.write().unwrap();
+            let mut changed = false;
+            for (_, entity) in chunk.block_entities_mut().filter(|(_, e)| e.is_ticking()) {
+                changed |= entity.tick(delta, &self.registry);
+            }
+            if changed {
+                chunk.set_modified();
+            }
+        }
+    }
+
+    /// Drops an item stack at a position (world space), giving it a small random velocity.
+    /// The item is lost if its chunk is not loaded
+    pub fn spawn_item(&mut self, position: [f32; 3], stack: ItemStack) {
+        let velocity = [
+            (self.item_rng.next_f32() - 0.5) * 3.0,
+            3.0 + self.item_rng.next_f32() * 2.0,
+            (self.item_rng.next_f32() - 0.5) * 3.0,
+        ];
+        let item = ItemEntity::new(position, velocity, stack);
+        let chunk_coord = item.block_coord().chunk();
+        match self.loader.chunk(&chunk_coord) {
+            Some(chunk) => chunk.write().unwrap().add_item(item),
+            None => println!("Dropped item in unloaded chunk"),
+        }
+    }
+
+    /// Moves the dropped items within simulation distance, merges nearby stacks,
+    /// despawns old items and moves the ones close to the player into their inventory
+    pub fn tick_items(&self, delta: f32, player: &mut Player) {
+        let mut items = Vec::new();
+        // Where and how big the items were in every chunk, to only mark chunks whose items changed as modified
+        let mut before: HashMap<ChunkPos, Vec<([f32; 3], ItemStack)>> = HashMap::new();
+        for (coord, chunk) in self.loader.chunks() {
+            if in_distance(player, coord, self.simulation_distance) && chunk.read().unwrap().has_items() {
+                let taken = chunk.write().unwrap().take_items();
+                let positions = taken.iter().map(|item| (item.position, item.stack.clone()));
+                before.insert(*coord, positions.collect());
+                items.extend(taken);
+            }
+        }
+        if items.is_empty() {
+            return;
+        }
+
+        // Unloaded blocks are treated as solid so items never leave the loaded chunks
+        let is_solid = |pos| self.get_block(pos).map(|block| !block.is_air()).unwrap_or(true);
+        for item in items.iter_mut() {
+            item.step(delta, is_solid);
+        }
+        items.retain(|item| item.age < self.item_despawn_time);
+        item_entity::merge_items(&mut items);
+
+        let pickup_box = player.aabb().loosened(PICKUP_RADIUS);
+        for item in items.iter_mut() {
+            if item.can_be_picked_up() && pickup_box.intersects(&item.aabb()) {
+                match player.inventory.add(item.stack.clone()) {
+                    None => item.stack.shrink(item.stack.count()),
+                    Some(leftover) => item.stack = leftover,
+                }
+            }
+        }
+
+        let mut after: HashMap<ChunkPos, Vec<ItemEntity>> = HashMap::new();
+        for item in items.into_iter().filter(|item| item.stack.count() > 0) {
+            after.entry(item.block_coord().chunk()).or_default().push(item);
+        }
+        let coords: HashSet<ChunkPos> = before.keys().chain(after.keys()).copied().collect();
+        for coord in coords {
+            let items = after.remove(&coord).unwrap_or_default();
+            let changed = match before.get(&coord) {
+                None => true,
+                Some(before) => {
+                    before.len() != items.len()
+                        || before.iter().zip(&items).any(|((position, stack), item)| {
+                            *position != item.position || *stack != item.stack
+                        })
+                }
+            };
+            if let Some(chunk) = self.loader.chunk(&coord) {
+                chunk.write().unwrap().put_items(items, changed);
+            }
+        }
+    }
+
+    /// Sets the area around the player that dropped items are simulated in
+    pub fn set_simulation_distance(&mut self, distance: LoadDistance) {
+        self.simulation_distance = distance;
+    }
+
+    /// Sets how many seconds dropped items stay in the world before they despawn
+    pub fn set_item_despawn_time(&mut self, seconds: f32) {
+        self.item_despawn_time = seconds;
+    }
+
+    /// Removes the queued load jobs of every chunk between `min` and `max` (inclusive, chunk space).
+    /// Returns the number of removed jobs
+    pub fn cancel_region(&mut self, min: &ChunkPos, max: &ChunkPos) -> usize {
+        self.loader.cancel_region(min, max)
+    }
+
+    /// Saves every loaded chunk that was modified and the time of the world
+    pub fn save_all(&self) {
+        self.loader.save_all();
+        WorldMeta { time: self.time }.save(&self.meta_path);
+    }
+}
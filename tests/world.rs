@@ -0,0 +1,367 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use glium::glutin::event::{ElementState, MouseButton, VirtualKeyCode};
+use vixen::actions::{Actions, Bindings};
+use vixen::block_entity::BlockEntity;
+use vixen::chunk::Block;
+use vixen::command;
+use vixen::daylight::DAY_LENGTH;
+use vixen::input::Input;
+use vixen::inventory::ItemStack;
+use vixen::loader::{in_distance, LoadDistance, LoadShape};
+use vixen::player::Player;
+use vixen::pos::{BlockPos, ChunkPos};
+use vixen::registry::Registry;
+use vixen::renderer::ChunkRenderer;
+use vixen::replay::{Outcome, Recording};
+use vixen::world::World;
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+fn registry() -> Arc<Registry> {
+    Arc::new(Registry::load(concat!(env!("CARGO_MANIFEST_DIR"), "/res")))
+}
+
+fn save_dir(name: &str) -> String {
+    let dir = std::env::temp_dir().join(format!("vixen-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir.to_string_lossy().into_owned()
+}
+
+/// Updates the world until `done` returns true
+fn update_until(world: &mut World, player: &Player, mut done: impl FnMut(&World) -> bool) {
+    let start = Instant::now();
+    while !done(world) {
+        assert!(start.elapsed() < TIMEOUT, "timed out waiting for the world");
+        world.update(player);
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn world_loads_chunks_without_window() {
+    let mut world = World::new(0, registry(), &save_dir("load"));
+    let player = Player::default();
+
+    update_until(&mut world, &player, |world| world.get_block(BlockPos::new(0, 0, 0)).is_some());
+    assert!(world.loader().get_number_of_loaded_chunks() > 0);
+}
+
+#[test]
+fn edited_blocks_are_saved_and_loaded() {
+    let dir = save_dir("edit");
+    let player = Player::default();
+    let pos = BlockPos::new(1, 2, 3);
+    // Last block of a chunk with negative coordinates
+    let negative = BlockPos::new(-1, -33, -32);
+
+    let mut world = World::new(0, registry(), &dir);
+    update_until(&mut world, &player, |world| {
+        world.get_block(pos).is_some() && world.get_block(negative).is_some()
+    });
+    world.set_block(pos, Block::new(5));
+    world.set_block(negative, Block::new(6));
+    assert_eq!(world.get_block(pos), Some(Block::new(5)));
+    assert_eq!(world.get_block(negative), Some(Block::new(6)));
+    assert_ne!(world.get_block(negative.offset(1, 0, 0)), Some(Block::new(6)));
+    world.save_all();
+    // Saved chunks aren't written again until they change
+    let chunk = world.loader().get_chunk(pos).unwrap();
+    assert!(!chunk.read().unwrap().is_modified());
+    world.set_block(pos, Block::new(6));
+    assert!(chunk.read().unwrap().is_modified());
+    world.set_block(pos, Block::new(5));
+    world.save_all();
+
+    let mut world = World::new(0, registry(), &dir);
+    update_until(&mut world, &player, |world| {
+        world.get_block(pos).is_some() && world.get_block(negative).is_some()
+    });
+    assert_eq!(world.get_block(pos), Some(Block::new(5)));
+    assert_eq!(world.get_block(negative), Some(Block::new(6)));
+}
+
+#[test]
+fn block_entities_are_saved_and_loaded() {
+    let dir = save_dir("block-entity");
+    let player = Player::default();
+    let chest = BlockPos::new(1, 2, 3);
+    let sign = BlockPos::new(-1, -33, -32);
+
+    let mut world = World::new(0, registry(), &dir);
+    update_until(&mut world, &player, |world| {
+        world.get_block(chest).is_some() && world.get_block(sign).is_some()
+    });
+    world.set_block(chest, Block::new(8));
+    world.set_block(sign, Block::new(10));
+    let stack = world.registry().items.stack(1, 5).unwrap();
+    world.with_block_entity_mut(chest, |entity| match entity {
+        BlockEntity::Chest(inventory) => inventory.add(stack.clone()).is_none(),
+        _ => false,
+    });
+    world.with_block_entity_mut(sign, |entity| match entity {
+        BlockEntity::Sign(text) => {
+            *text = "Hello".to_string();
+            true
+        }
+        _ => false,
+    });
+    world.save_all();
+
+    let mut world = World::new(0, registry(), &dir);
+    update_until(&mut world, &player, |world| {
+        world.get_block(chest).is_some() && world.get_block(sign).is_some()
+    });
+    assert!(world.has_block_entity(chest) && world.has_block_entity(sign));
+    assert!(!world.has_block_entity(chest.offset(1, 0, 0)));
+    // Closures that only look at the block entity return false
+    let mut stored = None;
+    world.with_block_entity_mut(chest, |entity| {
+        if let BlockEntity::Chest(inventory) = entity {
+            stored = inventory.get(0).cloned();
+        }
+        false
+    });
+    assert_eq!(stored, Some(stack));
+    let mut text = String::new();
+    world.with_block_entity_mut(sign, |entity| {
+        if let BlockEntity::Sign(sign) = entity {
+            text = sign.clone();
+        }
+        false
+    });
+    assert_eq!(text, "Hello");
+
+    // Only changes mark the chunk to be saved again
+    let chunk = world.loader().get_chunk(chest).unwrap();
+    assert!(!chunk.read().unwrap().is_modified());
+    world.with_block_entity_mut(chest, |_| true);
+    assert!(chunk.read().unwrap().is_modified());
+}
+
+#[test]
+fn edits_only_update_neighbors_sharing_the_border() {
+    let mut world = World::new(0, registry(), &save_dir("border"));
+    let player = Player::default();
+    let chunk = BlockPos::new(0, player.y as i32, 0).chunk();
+    let loaded = |world: &World| {
+        std::iter::once(chunk)
+            .chain(chunk.neighbors())
+            .all(|coord| world.loader().chunk(&coord).is_some())
+    };
+    update_until(&mut world, &player, loaded);
+
+    let needs_update = |world: &World| {
+        chunk
+            .neighbors()
+            .map(|coord| world.loader().chunk(&coord).unwrap().read().unwrap().needs_update())
+    };
+    let clear = |world: &World| {
+        for coord in std::iter::once(chunk).chain(chunk.neighbors()) {
+            world.loader().chunk(&coord).unwrap().write().unwrap().set_updated();
+        }
+    };
+
+    clear(&world);
+    world.set_block(chunk.origin().offset(5, 6, 7), Block::new(5));
+    assert!(world.loader().chunk(&chunk).unwrap().read().unwrap().needs_update());
+    assert_eq!(needs_update(&world), [false; 6]);
+
+    clear(&world);
+    world.set_block(chunk.origin().offset(31, 6, 0), Block::new(5));
+    assert_eq!(needs_update(&world), [true, false, false, false, false, true]);
+}
+
+#[test]
+fn unloaded_chunks_are_reused_from_cache() {
+    let mut world = World::new(0, registry(), &save_dir("cache"));
+    world.set_load_distance(LoadDistance::new(LoadShape::Cube, 1, 1));
+    let mut player = Player::default();
+    let pos = BlockPos::new(1, player.y as i32, 3);
+
+    update_until(&mut world, &player, |world| world.get_block(pos).is_some());
+    world.set_block(pos, Block::new(5));
+
+    player.x = 10.0 * 32.0;
+    update_until(&mut world, &player, |world| world.get_block(pos).is_none());
+    assert!(world.loader().chunk_cache_stats().entries > 0);
+
+    player.x = 0.0;
+    update_until(&mut world, &player, |world| world.get_block(pos).is_some());
+    assert_eq!(world.get_block(pos), Some(Block::new(5)));
+    assert!(world.loader().chunk_cache_stats().hits > 0);
+}
+
+#[test]
+fn renderer_generates_meshes_without_window() {
+    let registry = registry();
+    let mut world = World::new(0, registry.clone(), &save_dir("mesh"));
+    let texture_info: HashMap<u16, [[[f32; 2]; 4]; 6]> =
+        (0..=10).map(|id| (id, [[[0.0; 2]; 4]; 6])).collect();
+    let mut renderer = ChunkRenderer::new(Arc::new(texture_info));
+    let player = Player::default();
+
+    let start = Instant::now();
+    while renderer.get_number_of_pending_meshes() == 0 {
+        assert!(start.elapsed() < TIMEOUT, "timed out waiting for meshes");
+        world.update(&player);
+        renderer.update(&world, &player);
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn chunks_are_meshed_before_their_neighbors_load() {
+    let mut world = World::new(0, registry(), &save_dir("frontier"));
+    // Only the chunk of the player is loaded, so none of its neighbors ever are
+    world.set_load_distance(LoadDistance::new(LoadShape::Cube, 0, 0));
+    let texture_info: HashMap<u16, [[[f32; 2]; 4]; 6]> =
+        (0..=10).map(|id| (id, [[[0.0; 2]; 4]; 6])).collect();
+    let mut renderer = ChunkRenderer::new(Arc::new(texture_info));
+    let player = Player::default();
+    let pos = BlockPos::new(0, player.y as i32, 0);
+
+    update_until(&mut world, &player, |world| world.get_block(pos).is_some());
+    world.set_block(pos, Block::new(5));
+
+    let start = Instant::now();
+    while renderer.get_number_of_pending_meshes() == 0 {
+        assert!(start.elapsed() < TIMEOUT, "timed out waiting for meshes");
+        world.update(&player);
+        renderer.update(&world, &player);
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    assert_eq!(world.loader().get_number_of_loaded_chunks(), 1);
+}
+
+#[test]
+fn every_chunk_is_meshed_after_render_distance_changes() {
+    let mut world = World::new(0, registry(), &save_dir("resize"));
+    world.set_load_distance(LoadDistance::new(LoadShape::Cube, 2, 2));
+    let texture_info: HashMap<u16, [[[f32; 2]; 4]; 6]> =
+        (0..=10).map(|id| (id, [[[0.0; 2]; 4]; 6])).collect();
+    let mut renderer = ChunkRenderer::new(Arc::new(texture_info));
+    renderer.set_worker_threads(1);
+    let distance = LoadDistance::new(LoadShape::Cube, 2, 2);
+    renderer.set_render_distance(distance);
+    let player = Player::default();
+
+    world.set_blocking_loads(true);
+    world.update(&player);
+
+    // Shrinking the distance while meshes are queued skips their jobs
+    renderer.update(&world, &player);
+    renderer.set_render_distance(LoadDistance::new(LoadShape::Cube, 0, 0));
+    renderer.update(&world, &player);
+    renderer.set_render_distance(distance);
+    assert!(renderer.get_number_of_cancelled_meshes() > 0);
+
+    let start = Instant::now();
+    loop {
+        world.update(&player);
+        renderer.update(&world, &player);
+        let missing = world
+            .loader()
+            .chunks()
+            .iter()
+            .filter(|(coord, chunk)| !chunk.read().unwrap().is_empty() && in_distance(&player, coord, distance))
+            .filter(|(coord, _)| !renderer.has_mesh(coord))
+            .count();
+        if missing == 0 {
+            break;
+        }
+        assert!(start.elapsed() < TIMEOUT, "timed out waiting for {} meshes", missing);
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn recorded_input_replays_the_same_way() {
+    let distance = LoadDistance::new(LoadShape::Cube, 1, 1);
+    let mut world = World::new(3, registry(), &save_dir("record"));
+    world.set_load_distance(distance);
+    world.set_blocking_loads(true);
+    let mut player = Player::default();
+    let mut actions = Actions::new(Bindings::default());
+    let mut input = Input::new();
+    let mut recording = Recording::new(3, distance, Bindings::default(), &player);
+
+    // Walk forward while turning and looking down, then jump and mine
+    let delta = 1.0 / 60.0;
+    input.process_keyboard_event(ElementState::Pressed, VirtualKeyCode::W);
+    for tick in 0..120 {
+        input.update_mouse_motion((tick as f64 % 7.0, 3.0));
+        match tick {
+            60 => input.process_keyboard_event(ElementState::Pressed, VirtualKeyCode::Space),
+            70 => input.update_mouse_button(MouseButton::Left, ElementState::Pressed),
+            _ => (),
+        }
+        recording.record(delta, &input);
+        world.tick(&mut player, &mut actions, &input, delta);
+        input.reset_deltas();
+    }
+    recording.finish(&player, &world);
+    let outcome = recording.outcome.unwrap();
+    assert_ne!(outcome.position, [0.0; 3]);
+
+    let path = format!("{}/recording.dat", save_dir("recording-file"));
+    recording.save(&path);
+    let recording = Recording::load(&path).unwrap();
+    assert_eq!(recording.ticks.len(), 120);
+    assert_eq!(recording.replay(registry(), &save_dir("replay")), outcome);
+    assert_eq!(Some(outcome), recording.outcome);
+
+    // Different input ends up somewhere else
+    let mut changed = recording.clone();
+    changed.ticks.truncate(100);
+    let Outcome { position, .. } = changed.replay(registry(), &save_dir("replay-changed"));
+    assert_ne!(position, outcome.position);
+}
+
+#[test]
+fn time_advances_with_ticks_and_is_saved() {
+    let dir = save_dir("time");
+    let mut world = World::new(0, registry(), &dir);
+    world.set_load_distance(LoadDistance::new(LoadShape::Cube, 1, 1));
+    let mut player = Player::default();
+    let mut actions = Actions::new(Bindings::default());
+    let start = world.time();
+    for _ in 0..60 {
+        world.tick(&mut player, &mut actions, &Input::new(), 1.0 / 60.0);
+    }
+    assert!((world.time() - start - 1.0).abs() < 1e-3);
+
+    assert_eq!(command::run("/time set 18:00", &mut world), Ok("Set time to 18:00".to_string()));
+    assert_eq!(world.time_of_day(), 0.75);
+    // Setting an earlier time of day skips to the next day
+    let evening = world.time();
+    command::run("/time set noon", &mut world).unwrap();
+    assert!((world.time() - evening - DAY_LENGTH * 0.75).abs() < 1e-6);
+    assert!(command::run("/time set later", &mut world).is_err());
+    assert!(command::run("/weather clear", &mut world).is_err());
+
+    world.save_all();
+    let time = world.time();
+    drop(world);
+    assert_eq!(World::new(0, registry(), &dir).time(), time);
+}
+
+#[test]
+fn crafting_items_that_dont_fit_are_dropped() {
+    let mut world = World::new(0, registry(), &save_dir("crafting"));
+    world.set_load_distance(LoadDistance::new(LoadShape::Cube, 0, 0));
+    let mut player = Player::default();
+    update_until(&mut world, &player, |world| world.get_block(BlockPos::new(0, 1, 0)).is_some());
+
+    while player.inventory.add(ItemStack::new(1, 64, 64)).is_none() {}
+    assert!(!player.inventory.can_add(&ItemStack::new(2, 1, 64)));
+    player.crafting_grid.set(0, 0, Some(ItemStack::new(2, 3, 64)));
+    player.close_crafting_grids(&mut world);
+
+    assert!(player.crafting_grid.get(0, 0).is_none());
+    let chunk = world.loader().chunk(&ChunkPos::new(0, 0, 0)).unwrap().read().unwrap();
+    let dropped: Vec<&ItemStack> = chunk.items().iter().map(|item| &item.stack).collect();
+    assert_eq!(dropped, [&ItemStack::new(2, 3, 64)]);
+}